@@ -1,13 +1,24 @@
-mod animation;
+pub mod animation;
 mod character;
+mod character_select;
+mod combo;
+mod data_error;
+mod event_log;
 mod movelist;
 mod camera;
 pub mod fixed_point;
+mod hot_reload;
+mod hud;
 mod input;
+mod palette;
+mod projectile;
+mod render_mode;
 mod time;
+mod visual_effect;
 
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
     hash::{Hash, Hasher},
     marker::PhantomData,
     net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
@@ -17,7 +28,10 @@ use std::{
 
 use clap::ValueEnum;
 use ggrs::{GGRSError, P2PSession, SessionState, UdpNonBlockingSocket};
+use serde::{Deserialize, Serialize};
 use sdl2::{
+    event::Event,
+    keyboard::Scancode,
     pixels::Color,
     render::{Canvas, Texture, TextureCreator},
     video::{Window, WindowContext},
@@ -31,17 +45,42 @@ use time::Frame;
 
 use crate::state::StateTransition;
 
+pub use character::{Character, CharacterChoice};
+pub use character_select::CharacterSelect;
+pub use hot_reload::HotReload;
+pub use render_mode::RenderMode;
+
 use self::{
-    animation::Animation,
+    animation::{
+        AnimationId, AnimationStore, AttackPhase, CounterState, FrameEvent, LoopMode,
+    },
     character::{
-        BlockstunState, Character, CharacterDirection, CharacterProto, CharacterState, Facing,
-        HitstunState, IdleState, State, StateTransitionRequest, StateTransitionRequests,
+        BlockstunState, CharacterDirection, CharacterError, CharacterProto, CharacterSpecificState,
+        CharacterState,
+        CharacterStats, Facing,
+        GuardCrushState, HitstunState, IdleState, State, StateTransitionRequest, StateTransitionRequests,
     },
-    movelist::{Effect, HitEffect},
+    combo::{Combo, ComboScaling},
+    event_log::{EventLog, GameEvent},
+    movelist::{CounterKind, CounterModifiers, Effect, HitEffect},
+    projectile::{Projectile, ProjectileSpawn},
+    render_mode::{SceneTarget, SCENE_HEIGHT, SCENE_WIDTH},
+    visual_effect::VisualEffect,
 };
 
 pub const FPS: usize = 60;
 
+/// Distance from the center of the stage to each of the walls
+pub const STAGE_HALF_WIDTH: usize = 200;
+
+pub const MAX_METER: usize = 100;
+
+pub const MAX_GUARD: usize = 100;
+/// Frames after taking guard damage before the guard gauge starts to regenerate
+pub const GUARD_REGEN_DELAY: usize = 90;
+/// Frames of stun when the guard gauge is emptied
+pub const GUARD_CRUSH_STUN: usize = 60;
+
 pub enum PlayerType {
     Local {
         mapping: InputMapping,
@@ -52,19 +91,39 @@ pub enum PlayerType {
 
 pub struct GameInfo {
     pub character_protos: HashMap<Character, CharacterProto>,
-    pub bg_texture: Texture<'static>,
+    pub animations: AnimationStore,
+    /// `None` without a window
+    pub bg_texture: Option<Texture>,
     pub player_1: PlayerType,
     pub player_2: PlayerType,
     pub waiting_for_network: bool,
+    pub combo_scaling: ComboScaling,
+    /// Whether chip damage is allowed to KO
+    pub chip_can_kill: bool,
+    /// Set in dev mode
+    pub hot_reload: Option<HotReload>,
+    /// Switched between with F4 during a match
+    pub render_mode: RenderMode,
+    /// Kept out of the game state so it isn't copied into every rollback save. Rewound instead
+    /// when an older state is loaded.
+    pub event_log: RefCell<EventLog>,
 }
 
 impl GameInfo {
-    pub fn create(texture_creator: &'static TextureCreator<WindowContext>) -> Self {
-        Self {
-            character_protos: [(Character::Guy, CharacterProto::create_guy(texture_creator))]
+    pub fn create(
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Self, CharacterError> {
+        let mut animations = AnimationStore::new();
+        Ok(Self {
+            character_protos: Character::ALL
                 .into_iter()
-                .collect(),
-            bg_texture: {
+                .map(|character| {
+                    let proto = CharacterProto::create(character, &mut animations, texture_creator)?;
+                    Ok((character, proto))
+                })
+                .collect::<Result<_, CharacterError>>()?,
+            animations,
+            bg_texture: texture_creator.map(|texture_creator| {
                 let img = image::load_from_memory(&std::fs::read("assets/sprites/bg.png").unwrap())
                     .unwrap()
                     .into_rgba8();
@@ -79,7 +138,7 @@ impl GameInfo {
                 texture.set_blend_mode(sdl2::render::BlendMode::Blend);
                 texture.update(None, &img, 1920 * 4).unwrap();
                 texture
-            },
+            }),
             player_1: PlayerType::Local {
                 mapping: InputMapping::default_keyboard(),
                 input: BoxedInput::new(),
@@ -89,6 +148,39 @@ impl GameInfo {
                 input: BoxedInput::new(),
             },
             waiting_for_network: false,
+            combo_scaling: ComboScaling {
+                decay_per_hit: FixedPoint::from(0.1),
+                minimum: FixedPoint::from(0.3),
+            },
+            chip_can_kill: false,
+            hot_reload: None,
+            render_mode: RenderMode::Direct,
+            event_log: RefCell::new(EventLog::new()),
+        })
+    }
+
+    /// Feeds a raw input to every local player whose mapping uses it
+    pub fn input(&mut self, input: impl Into<Input>, pressed: bool) {
+        let input = input.into();
+
+        let players = [&mut self.player_1, &mut self.player_2];
+
+        for player in players {
+            match player {
+                PlayerType::Local {
+                    mapping,
+                    input: boxed_input,
+                } => {
+                    if let Some(action) = mapping.get_action(input) {
+                        if pressed {
+                            boxed_input.press(action);
+                        } else {
+                            boxed_input.release(action);
+                        }
+                    }
+                }
+                PlayerType::Remote => {}
+            }
         }
     }
 }
@@ -97,53 +189,30 @@ impl GameInfo {
 pub struct GameState {
     player_1: Player,
     player_2: Player,
+    projectiles: Vec<Projectile>,
+    /// Not part of the checksum, since they don't affect the match
+    effects: Vec<VisualEffect>,
 
     camera: Camera,
 
     current_frame: Frame,
+    /// Frames left of the super freeze, during which neither player is simulated
+    super_freeze: usize,
+    /// Last counter hit, shown on screen for a while
+    counter_indicator: Option<(PlayerSide, CounterKind, Frame)>,
 }
 
 impl GameState {
-    pub fn new(game_info: &GameInfo) -> Self {
+    /// Starts a match between the given characters. The second player's palette is changed if
+    /// both players would otherwise look the same.
+    pub fn new(game_info: &GameInfo, player_1: CharacterChoice, player_2: CharacterChoice) -> Self {
+        let palette_count = game_info.character_protos[&player_2.character].palettes.len();
+        let player_2 = player_2.distinct_from(player_1, palette_count);
         Self {
-            player_1: Player {
-                health: FixedPoint::from(100),
-                position: Vec2::new((-50.0).try_into().unwrap(), 0.0.try_into().unwrap()),
-                velocity: Vec2::new(FixedPoint::from(0), FixedPoint::from(0)),
-                character: Character::Guy,
-                last_input: BoxedInput::new(),
-                input_history: InputHistory::new(),
-                animator: Animator::new(
-                    Frame::new(),
-                    &game_info.character_protos[&Character::Guy].animations["idle"],
-                ),
-                current_state: IdleState.wrap(),
-                state_transition_requests: StateTransitionRequests::new(),
-                hurtboxes: vec![],
-                hitboxes: vec![],
-                current_attack: None,
-                facing: Facing::Right,
-                grounded: true,
-            },
-            player_2: Player {
-                health: FixedPoint::from(100),
-                position: Vec2::new((50.0).try_into().unwrap(), 0.0.try_into().unwrap()),
-                velocity: Vec2::new(FixedPoint::from(0), FixedPoint::from(0)),
-                character: Character::Guy,
-                last_input: BoxedInput::new(),
-                input_history: InputHistory::new(),
-                animator: Animator::new(
-                    Frame::new(),
-                    &game_info.character_protos[&Character::Guy].animations["idle"],
-                ),
-                current_state: IdleState.wrap(),
-                state_transition_requests: StateTransitionRequests::new(),
-                hurtboxes: vec![],
-                hitboxes: vec![],
-                current_attack: None,
-                facing: Facing::Left,
-                grounded: true,
-            },
+            player_1: Player::new(game_info, player_1, FixedPoint::from(-50.0), Facing::Right),
+            player_2: Player::new(game_info, player_2, FixedPoint::from(50.0), Facing::Left),
+            projectiles: vec![],
+            effects: vec![],
             camera: Camera {
                 center: FVec2::new(0.0, 30.0),
                 scale: 5.0,
@@ -152,7 +221,50 @@ impl GameState {
                 offset: FVec2::new(0.0, 0.0),
             },
             current_frame: Frame::new(),
+            super_freeze: 0,
+            counter_indicator: None,
+        }
+    }
+
+    /// Fixes up anything that refers to data that was hot reloaded and no longer exists
+    pub fn reloaded(&mut self, game_info: &GameInfo) {
+        let frame = self.current_frame;
+        for player in [&mut self.player_1, &mut self.player_2] {
+            player.animator.clamp_frame(&game_info.animations);
+
+            // Scripted states are referred to by index, so a removed state can't be kept
+            let proto = &game_info.character_protos[&player.character];
+            let removed = matches!(
+                &player.current_state,
+                CharacterState::CharacterSpecific(CharacterSpecificState::Scripted(state))
+                    if state.id >= proto.scripted_states.len()
+            );
+            if removed {
+                player.current_state = IdleState.wrap();
+                player.current_attack = None;
+                player
+                    .animator
+                    .switch_animation(frame, proto.animations["idle"], None);
+            }
+        }
+        for projectile in &mut self.projectiles {
+            projectile.animator.clamp_frame(&game_info.animations);
         }
+        for effect in &mut self.effects {
+            effect.clamp_frame(&game_info.animations);
+        }
+    }
+
+    /// Hash of the parts of the state that decide how the match plays out. Compared between
+    /// peers to detect desyncs.
+    pub fn checksum(&self) -> u128 {
+        let mut hasher = DefaultHasher::new();
+        self.current_frame.hash(&mut hasher);
+        self.super_freeze.hash(&mut hasher);
+        self.player_1.hash(&mut hasher);
+        self.player_2.hash(&mut hasher);
+        self.projectiles.hash(&mut hasher);
+        hasher.finish() as u128
     }
 
     pub fn player(&self, player: PlayerSide) -> &Player {
@@ -215,18 +327,136 @@ impl GameState {
         let frame = self.current_frame;
         let player = self.player_mut(player_side);
         let proto = &game_info.character_protos[&player.character];
-        if let Some(mov) =
-            proto
-                .movelist
-                .perform(frame, &player.current_state, &player.input_history)
-        {
+        if let Some(mov) = proto.movelist.perform(
+            frame,
+            &player.current_state,
+            &player.input_history,
+            player.meter,
+            player.animator.cancel_window,
+        ) {
             player
                 .state_transition_requests
                 .add(StateTransitionRequest::new(mov.new_state.clone(), None));
             if mov.stops_momentum {
                 player.velocity = Vec2::new(FixedPoint::ZERO, FixedPoint::ZERO);
             }
+            player.meter -= mov.meter_cost;
+            player.gain_meter(mov.meter_gain);
+            if mov.super_freeze > 0 {
+                self.super_freeze = mov.super_freeze;
+            }
+            game_info.event_log.borrow_mut().push(
+                frame,
+                GameEvent::MovePerformed {
+                    player: player_side,
+                    name: mov.name.clone(),
+                },
+            );
+            for effect in &mov.self_effects {
+                self.apply_effect(player_side, effect, game_info);
+            }
+        }
+    }
+
+    fn apply_effect(&mut self, player_side: PlayerSide, effect: &Effect, game_info: &GameInfo) {
+        game_info.event_log.borrow_mut().push(
+            self.current_frame,
+            GameEvent::EffectApplied {
+                player: player_side,
+                effect: effect.clone(),
+            },
+        );
+
+        match effect {
+            Effect::ChipDamage(amt) => {
+                let player = self.player_mut(player_side);
+                let amt = if game_info.chip_can_kill {
+                    *amt
+                } else {
+                    (*amt).min(player.health - FixedPoint::ONE).max(FixedPoint::ZERO)
+                };
+                let stats = &game_info.character_protos[&player.character].stats;
+                player.apply_effect(&Effect::Damage(amt), stats);
+            }
+            _ => {
+                let player = self.player_mut(player_side);
+                let stats = &game_info.character_protos[&player.character].stats;
+                player.apply_effect(effect, stats)
+            }
+        }
+    }
+
+    /// Handles an event of a frame a player's animation entered, other than the ones the
+    /// animator handles itself
+    fn frame_event(&mut self, player_side: PlayerSide, event: FrameEvent, game_info: &GameInfo) {
+        let frame = self.current_frame;
+        let player = self.player(player_side);
+        let proto = &game_info.character_protos[&player.character];
+
+        match &event {
+            // The names were checked against the character when the animation was loaded
+            FrameEvent::SpawnEffect { animation, offset } => {
+                let mut offset = *offset;
+                if player.facing == Facing::Left {
+                    offset.x = -offset.x;
+                }
+                let effect = VisualEffect::new(
+                    &game_info.animations,
+                    proto.animations[animation.as_str()],
+                    frame,
+                    player.position + offset,
+                    player.facing,
+                    player.palette,
+                );
+                self.effects.push(effect);
+            }
+            FrameEvent::SpawnProjectile(name) => {
+                self.spawn_projectile(player_side, &proto.projectiles[name.as_str()], game_info)
+            }
+            _ => {}
+        }
+
+        game_info.event_log.borrow_mut().push(
+            frame,
+            GameEvent::FrameEvent {
+                player: player_side,
+                event,
+            },
+        );
+    }
+
+    fn spawn_projectile(
+        &mut self,
+        owner: PlayerSide,
+        spawn: &ProjectileSpawn,
+        game_info: &GameInfo,
+    ) {
+        let frame = self.current_frame;
+        let player = self.player(owner);
+        let proto = &game_info.character_protos[&player.character];
+
+        let (mut offset, mut velocity) = (spawn.offset, spawn.velocity);
+        if player.facing == Facing::Left {
+            offset.x = -offset.x;
+            velocity.x = -velocity.x;
         }
+
+        let projectile = Projectile {
+            owner,
+            animator: Animator::new(frame, proto.animations[spawn.animation]),
+            position: player.position + offset,
+            velocity,
+            facing: player.facing,
+            palette: player.palette,
+            hitboxes: vec![],
+            hurtboxes: vec![],
+            hit_effect: spawn.hit_effect.clone(),
+            hits_left: spawn.hits,
+            hit_interval: spawn.hit_interval,
+            last_hit: None,
+            lifetime: spawn.lifetime,
+        };
+        self.projectiles.push(projectile);
     }
 
     fn state_transitions(&mut self, game_info: &GameInfo) {
@@ -235,23 +465,45 @@ impl GameState {
     }
 
     fn handle_player_state_transitions(&mut self, game_info: &GameInfo, player_side: PlayerSide) {
+        let frame = self.current_frame;
         let player = self.player_mut(player_side);
         let Some(req) = player.state_transition_requests.take() else {
             return;
         };
 
-        println!("Transitioning to {:?}", req.state);
-
         if req.insert_priority > player.current_state.priority() {
             if let Some(cmd) = player.current_state.on_exit(player_side) {
                 cmd(self, game_info)
             }
             let player = self.player_mut(player_side);
-            player.current_state = req.state;
+            let from = std::mem::replace(&mut player.current_state, req.state.clone());
+            game_info.event_log.borrow_mut().push(
+                frame,
+                GameEvent::StateTransition {
+                    player: player_side,
+                    from,
+                    to: req.state,
+                    priority: req.insert_priority,
+                    requester: req.requester,
+                },
+            );
 
+            let player = self.player_mut(player_side);
             if let Some(cmd) = player.current_state.on_enter(player_side) {
                 cmd(self, game_info)
             }
+        } else {
+            let current = player.current_state.clone();
+            game_info.event_log.borrow_mut().push(
+                frame,
+                GameEvent::TransitionRejected {
+                    player: player_side,
+                    current,
+                    requested: req.state,
+                    priority: req.insert_priority,
+                    requester: req.requester,
+                },
+            );
         }
     }
 
@@ -261,6 +513,10 @@ impl GameState {
 
         let mut hits = vec![];
 
+        if target.is_invincible() {
+            return hits;
+        }
+
         for hitbox in &attacker.hitboxes {
             for hurtbox in &target.hurtboxes {
                 if hitbox
@@ -272,12 +528,20 @@ impl GameState {
                         attacker: player_side,
                         target: player_side.reverse(),
                         attacker_tag: hitbox.tag.clone(),
-                        target_tag: hurtbox.tag.clone(),
                     });
                 }
             }
         }
 
+        // Make sure the best matching hitbox group gets to land the hit
+        if let Some(attack) = &attacker.current_attack {
+            hits.sort_by_key(|hit| {
+                attack
+                    .tagged_hit_effect(&hit.attacker_tag)
+                    .map_or(usize::MAX, |(index, _)| index)
+            });
+        }
+
         hits
     }
 
@@ -286,56 +550,215 @@ impl GameState {
         hits.append(&mut self.handle_player_hitreg(PlayerSide::Player1));
         hits.append(&mut self.handle_player_hitreg(PlayerSide::Player2));
 
+        let frame = self.current_frame;
+
         for hit in hits {
             let Some(attack) = &mut self.player_mut(hit.attacker).current_attack else {
                 continue;
             };
-            if attack.has_hit_player {
+            if !attack.can_hit(frame) {
                 continue;
             }
-            attack.has_hit_player = true;
+            attack.register_hit(frame);
             let Some(attack) = &self.player(hit.attacker).current_attack else {
                 continue;
             };
 
-            let attacker = self.player(hit.attacker);
+            let hit_effect = attack.hit_effect(&hit.attacker_tag);
             let target = self.player(hit.target);
 
-            let blocked = !attack.hit_effect.unblockable && target.is_blocking(self.current_frame);
+            let blocked = !hit_effect.unblockable && target.is_blocking(frame);
+            let counter = if blocked {
+                None
+            } else {
+                target.counter_kind(&game_info.animations, frame)
+            };
+            let modifiers = match counter {
+                Some(CounterKind::CounterHit) => hit_effect.counter_hit,
+                Some(CounterKind::PunishCounter) => hit_effect.punish_counter,
+                None => CounterModifiers::NONE,
+            };
+
+            let starter_scaling = hit_effect.starter_scaling;
+            let (effects, self_effects, meter_gain) = if blocked {
+                (
+                    hit_effect.effects_on_block.clone(),
+                    hit_effect.self_effects_on_block.clone(),
+                    hit_effect.meter_gain_on_block,
+                )
+            } else {
+                (
+                    hit_effect.effects_on_hit.clone(),
+                    hit_effect.self_effects_on_hit.clone(),
+                    hit_effect.meter_gain_on_hit,
+                )
+            };
+
+            game_info.event_log.borrow_mut().push(
+                frame,
+                GameEvent::Hit {
+                    attacker: hit.attacker,
+                    target: hit.target,
+                    blocked,
+                    counter,
+                    projectile: false,
+                },
+            );
+            if let Some(counter) = counter {
+                self.counter_indicator = Some((hit.attacker, counter, frame));
+            }
+
+            self.player_mut(hit.attacker).gain_meter(meter_gain);
+
+            let scaling = if blocked {
+                FixedPoint::ONE
+            } else {
+                self.player_mut(hit.target)
+                    .combo
+                    .register_hit(starter_scaling, &game_info.combo_scaling)
+            };
+
+            for effect in effects {
+                let effect = Combo::scale(&modifiers.apply(&effect), scaling);
+                self.apply_effect(hit.target, &effect, game_info);
+            }
+            for effect in self_effects {
+                self.apply_effect(hit.attacker, &effect, game_info);
+            }
+        }
+
+        self.projectile_hitreg(game_info);
+    }
+
+    fn projectile_hitreg(&mut self, game_info: &GameInfo) {
+        let frame = self.current_frame;
+
+        // Projectiles of different owners clash and cancel out one hit each
+        for i in 0..self.projectiles.len() {
+            for j in i + 1..self.projectiles.len() {
+                let (a, b) = (&self.projectiles[i], &self.projectiles[j]);
+                if a.owner == b.owner || !a.can_hit(frame) || !b.can_hit(frame) {
+                    continue;
+                }
+                let clashes = a.hitboxes.iter().any(|ha| {
+                    b.hitboxes.iter().any(|hb| {
+                        ha.rect
+                            .offset(a.position)
+                            .overlaps(hb.rect.offset(b.position))
+                    })
+                });
+                if clashes {
+                    self.projectiles[i].register_hit(frame);
+                    self.projectiles[j].register_hit(frame);
+                }
+            }
+        }
+
+        for i in 0..self.projectiles.len() {
+            let projectile = &self.projectiles[i];
+            let target_side = projectile.owner.reverse();
+            let target = self.player(target_side);
+            if !projectile.can_hit(frame) || target.is_invincible() {
+                continue;
+            }
+
+            let hit = projectile.hitboxes.iter().any(|hitbox| {
+                target.hurtboxes.iter().any(|hurtbox| {
+                    hitbox
+                        .rect
+                        .offset(projectile.position)
+                        .overlaps(hurtbox.rect.offset(target.position))
+                })
+            });
+            if !hit {
+                continue;
+            }
+
+            let blocked = !projectile.hit_effect.unblockable && target.is_blocking(frame);
+            let hit_effect = projectile.hit_effect.clone();
+            let owner = projectile.owner;
+            self.projectiles[i].register_hit(frame);
+            game_info.event_log.borrow_mut().push(
+                frame,
+                GameEvent::Hit {
+                    attacker: owner,
+                    target: target_side,
+                    blocked,
+                    counter: None,
+                    projectile: true,
+                },
+            );
+
+            let (effects, meter_gain) = if blocked {
+                (hit_effect.effects_on_block, hit_effect.meter_gain_on_block)
+            } else {
+                (hit_effect.effects_on_hit, hit_effect.meter_gain_on_hit)
+            };
 
-            let effects = if blocked {
-                &attack.hit_effect.effects_on_block
+            self.player_mut(owner).gain_meter(meter_gain);
+
+            let scaling = if blocked {
+                FixedPoint::ONE
             } else {
-                &attack.hit_effect.effects_on_hit
+                self.player_mut(target_side)
+                    .combo
+                    .register_hit(hit_effect.starter_scaling, &game_info.combo_scaling)
             };
 
-            for effect in effects.clone() {
-                self.player_mut(hit.target).apply_effect(&effect);
+            for effect in effects {
+                self.apply_effect(target_side, &Combo::scale(&effect, scaling), game_info);
             }
         }
+
+        self.projectiles.retain(Projectile::is_alive);
     }
 
-    fn player_physics(&mut self) {
+    fn player_physics(&mut self, game_info: &GameInfo) {
         let delta = FixedPoint::from(1.0 / 60.0);
 
-        let deccel = FixedPoint::from(100.0) * delta;
+        let wall = FixedPoint::from(STAGE_HALF_WIDTH);
 
         for player in [&mut self.player_1, &mut self.player_2] {
+            let stats = &game_info.character_protos[&player.character].stats;
+            let deccel = stats.friction * delta;
+
             player.position += player.velocity * delta;
-            player.velocity.y += FixedPoint::from(400.0) * delta;
+            player.velocity.y += stats.gravity * delta;
             if player.position.y > FixedPoint::ZERO {
                 player.position.y = FixedPoint::ZERO;
-                player.velocity.y = FixedPoint::ZERO;
-                player.grounded = true;
-                if matches!(player.current_state, CharacterState::Airborne(_)) {
-                    player
-                        .state_transition_requests
-                        .add(StateTransitionRequest::new(IdleState.wrap(), 10000));
+                if let Some(bounce) = player.ground_bounce.take() {
+                    player.velocity.y = -bounce;
+                } else {
+                    player.velocity.y = FixedPoint::ZERO;
+                    player.grounded = true;
+                    player.air_jumps_left = stats.air_jumps;
+                    player.air_dash_used = false;
+                    if matches!(
+                        player.current_state,
+                        CharacterState::Airborne(_) | CharacterState::AirDash(_)
+                    ) {
+                        player
+                            .state_transition_requests
+                            .add(StateTransitionRequest::new(IdleState.wrap(), 10000));
+                    }
                 }
             } else if player.position.y < FixedPoint::ZERO {
                 player.grounded = false;
             }
 
+            if player.position.x.abs() > wall {
+                player.position.x = if player.position.x > FixedPoint::ZERO {
+                    wall
+                } else {
+                    -wall
+                };
+                if let Some(bounce) = player.wall_bounce.take() {
+                    player.velocity.x = -player.velocity.x;
+                    player.velocity.y = -bounce;
+                    player.grounded = false;
+                }
+            }
+
             if player.grounded {
                 if player.velocity.x.abs() <= deccel {
                     player.velocity.x = FixedPoint::ZERO
@@ -349,10 +772,17 @@ impl GameState {
     }
 
     pub fn tick(&mut self, game_info: &GameInfo) {
-        if let Some(req) = self.player_1.animator.pre_tick(self.current_frame) {
+        if self.super_freeze > 0 {
+            self.super_freeze -= 1;
+            self.current_frame.tick(true);
+            return;
+        }
+
+        let animations = &game_info.animations;
+        if let Some(req) = self.player_1.animator.pre_tick(animations, self.current_frame) {
             self.player_1.state_transition_requests.add(req);
         }
-        if let Some(req) = self.player_2.animator.pre_tick(self.current_frame) {
+        if let Some(req) = self.player_2.animator.pre_tick(animations, self.current_frame) {
             self.player_2.state_transition_requests.add(req);
         }
 
@@ -368,22 +798,38 @@ impl GameState {
 
         self.state_transitions(game_info);
 
-        self.player_1.animator.tick(
-            self.player_1.facing,
-            &mut self.player_1.hitboxes,
-            &mut self.player_1.hurtboxes,
-            self.current_frame,
-        );
-        self.player_2.animator.tick(
-            self.player_2.facing,
-            &mut self.player_2.hitboxes,
-            &mut self.player_2.hurtboxes,
-            self.current_frame,
-        );
+        // Root motion is clamped to the stage walls along with the velocity in `player_physics`
+        let mut frame_events = vec![];
+        for (player_side, player) in [
+            (PlayerSide::Player1, &mut self.player_1),
+            (PlayerSide::Player2, &mut self.player_2),
+        ] {
+            let tick = player.animator.tick(
+                &game_info.animations,
+                player.facing,
+                &mut player.hitboxes,
+                &mut player.hurtboxes,
+                self.current_frame,
+            );
+            player.position += tick.root_motion;
+            frame_events.extend(tick.events.into_iter().map(|event| (player_side, event)));
+        }
+        for (player_side, event) in frame_events {
+            self.frame_event(player_side, event, game_info);
+        }
 
         self.state_transitions(game_info);
 
-        self.player_physics();
+        self.player_physics(game_info);
+
+        let frame = self.current_frame;
+        for projectile in &mut self.projectiles {
+            projectile.tick(&game_info.animations, frame);
+        }
+        for effect in &mut self.effects {
+            effect.tick(&game_info.animations, frame);
+        }
+        self.effects.retain(VisualEffect::is_alive);
 
         self.state_transitions(game_info);
 
@@ -391,6 +837,14 @@ impl GameState {
 
         self.state_transitions(game_info);
 
+        let frame = self.current_frame;
+        for player in [&mut self.player_1, &mut self.player_2] {
+            if !player.is_in_hitstun() {
+                player.combo.end(frame);
+            }
+            player.regenerate_guard();
+        }
+
         let p1: FVec2 = self.player_1.position.into();
         let p2: FVec2 = self.player_2.position.into();
 
@@ -401,60 +855,54 @@ impl GameState {
         self.current_frame.tick(false);
     }
 
-    pub fn render(&mut self, game_info: &GameInfo, canvas: &mut Canvas<Window>) {
+    /// Draws the match to the window, with the scene scaled according to the render mode
+    pub fn render(
+        &mut self,
+        game_info: &GameInfo,
+        scene_target: &mut SceneTarget,
+        canvas: &mut Canvas<Window>,
+    ) {
+        let mode = game_info.render_mode;
         canvas.set_draw_color(Color::BLACK);
         canvas.clear();
 
         let (width, height) = canvas.window().size();
-
-        let aspect = width as f32 / height as f32;
-        let target_aspect = 16.0 / 9.0;
-
-        let (width, height) = if aspect > target_aspect {
-            let logical_height = height;
-            let logical_width = (height as f32 * target_aspect) as u32;
-
-            let offset = (width - logical_width) / 2;
-
-            canvas.set_clip_rect(sdl2::rect::Rect::new(
-                offset as _,
-                0,
-                logical_width,
-                logical_height,
-            ));
-
-            self.camera.offset = FVec2::new(offset as _, 0.0);
-
-            (logical_width, logical_height)
+        let viewport = mode.viewport(width, height);
+        canvas.set_clip_rect(viewport);
+
+        self.camera.width = viewport.width() as f32;
+        self.camera.height = viewport.height() as f32;
+        self.camera.offset = FVec2::new(viewport.x() as _, viewport.y() as _);
+        self.camera.scale = self.camera.width / SCENE_WIDTH as f32;
+
+        if mode.uses_target() {
+            // Whole world units line up with pixels, so sprites are never split unevenly
+            let camera = Camera {
+                center: FVec2::new(self.camera.center.x.round(), self.camera.center.y.round()),
+                scale: 1.0,
+                width: SCENE_WIDTH as f32,
+                height: SCENE_HEIGHT as f32,
+                offset: FVec2::new(0.0, 0.0),
+            };
+            scene_target.render(canvas, viewport, |canvas| {
+                self.render_scene(&camera, game_info, canvas)
+            });
         } else {
-            let logical_height = (width as f32 / target_aspect) as u32;
-            let logical_width = width;
-
-            let offset = (height - logical_height) / 2;
-
-            canvas.set_clip_rect(sdl2::rect::Rect::new(
-                0,
-                offset as _,
-                logical_width,
-                logical_height,
-            ));
+            self.render_scene(&self.camera, game_info, canvas);
+        }
 
-            self.camera.offset = FVec2::new(0.0, offset as _);
+        self.render_hud(game_info, canvas);
+    }
 
-            (logical_width, logical_height)
-        };
+    /// Draws the stage and everything in it
+    fn render_scene(&self, camera: &Camera, game_info: &GameInfo, canvas: &mut Canvas<Window>) {
         canvas.set_draw_color(Color::WHITE);
         canvas.fill_rect(None).unwrap();
 
-        self.camera.width = width as f32;
-        self.camera.height = height as f32;
-
-        self.camera.scale = self.camera.width / 1280.0 * 5.0;
-
         let bg_pos = FVec2::new(0.0, -354.0);
         let offset = FVec2::new(1920.0 / 2.0, 1080.0 / 2.0);
-        let min = self.camera.to_screen_space(bg_pos - offset);
-        let max = self.camera.to_screen_space(bg_pos + offset);
+        let min = camera.to_screen_space(bg_pos - offset);
+        let max = camera.to_screen_space(bg_pos + offset);
 
         let rect = sdl2::rect::Rect::new(
             min.x as _,
@@ -463,68 +911,30 @@ impl GameState {
             (max.y - min.y) as _,
         );
 
-        canvas.copy(&game_info.bg_texture, None, rect).unwrap();
-
-        let w = width as i32;
-        let h = height as i32;
-
-        self.player_1.render(&self.camera, game_info, canvas);
-        self.player_2.render(&self.camera, game_info, canvas);
-
-        let margin = 50;
-        let space_between = 200;
-        let health_width = (w - margin * 2 - space_between) / 2;
-
-        let p1hx = margin;
-        let p2hx = margin + health_width + space_between;
-
-        let hp_height = (10.0 * self.camera.scale) as _;
+        if let Some(bg_texture) = &game_info.bg_texture {
+            canvas.copy(bg_texture, None, rect).unwrap();
+        }
 
-        canvas.set_draw_color(Color::RGB(127, 127, 127));
-        canvas
-            .fill_rect(sdl2::rect::Rect::new(
-                p1hx + self.camera.offset.x as i32,
-                margin + self.camera.offset.y as i32,
-                health_width as _,
-                hp_height,
-            ))
-            .unwrap();
-        canvas
-            .fill_rect(sdl2::rect::Rect::new(
-                p2hx + self.camera.offset.x as i32,
-                margin + self.camera.offset.y as i32,
-                health_width as _,
-                hp_height,
-            ))
-            .unwrap();
+        if self.super_freeze > 0 {
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            canvas.fill_rect(None).unwrap();
+        }
 
-        let p1h = ((f64::from(self.player_1.health) / 100.0) * health_width as f64) as u32;
-        let p2h = ((f64::from(self.player_2.health) / 100.0) * health_width as f64) as u32;
+        self.player_1.render(camera, game_info, canvas);
+        self.player_2.render(camera, game_info, canvas);
 
-        let p2hx = p2hx + health_width as i32 - p2h as i32;
+        for projectile in &self.projectiles {
+            projectile.render(camera, &game_info.animations, canvas);
+        }
 
-        canvas.set_draw_color(Color::RGB(255, 0, 0));
-        canvas
-            .fill_rect(sdl2::rect::Rect::new(
-                p1hx + self.camera.offset.x as i32,
-                margin + self.camera.offset.y as i32,
-                p1h,
-                hp_height,
-            ))
-            .unwrap();
-        canvas
-            .fill_rect(sdl2::rect::Rect::new(
-                p2hx + self.camera.offset.x as i32,
-                margin + self.camera.offset.y as i32,
-                p2h,
-                hp_height,
-            ))
-            .unwrap();
+        for effect in &self.effects {
+            effect.render(camera, &game_info.animations, canvas);
+        }
 
         if game_info.waiting_for_network {
             let offset = FVec2::new(25.0, 25.0);
-            let min = self.camera.to_screen_space(self.camera.center - offset);
-            let max = self.camera.to_screen_space(self.camera.center + offset);
+            let min = camera.to_screen_space(camera.center - offset);
+            let max = camera.to_screen_space(camera.center + offset);
 
             let x = min.x;
             let y = min.y;
@@ -534,8 +944,109 @@ impl GameState {
             canvas.set_draw_color(Color::RGBA(255, 0, 0, 127));
             canvas.fill_rect(sdl2::rect::Rect::new(x as _, y as _, w as _, h as _)).unwrap();
         }
+    }
+
+    /// Draws the health, meter and guard bars and the indicators under them, at the window's
+    /// resolution
+    fn render_hud(&self, game_info: &GameInfo, canvas: &mut Canvas<Window>) {
+        let w = self.camera.width as i32;
+
+        let margin = 50;
+        let space_between = 200;
+        let health_width = (w - margin * 2 - space_between) / 2;
+
+        let p1hx = margin;
+        let p2hx = margin + health_width + space_between;
+
+        let hp_height = (10.0 * self.camera.scale) as u32;
+        let meter_y = margin + hp_height as i32 + hp_height as i32 / 2;
+        let meter_height = hp_height / 2;
+        let guard_y = meter_y + meter_height as i32 + meter_height as i32 / 2;
+        let guard_height = meter_height / 2;
+
+        // Each player's bar at the given height, with player 2's filling from the right
+        let bars = |y: i32, height: u32| {
+            [(PlayerSide::Player1, p1hx), (PlayerSide::Player2, p2hx)].map(|(side, x)| {
+                let area = sdl2::rect::Rect::new(
+                    x + self.camera.offset.x as i32,
+                    y + self.camera.offset.y as i32,
+                    health_width as u32,
+                    height,
+                );
+                (self.player(side), area, side == PlayerSide::Player2)
+            })
+        };
+        let background = Color::RGB(127, 127, 127);
+
+        for (player, area, from_right) in bars(margin, hp_height) {
+            let max = f64::from(game_info.character_protos[&player.character].stats.health);
+            let health = f64::from(player.health) / max;
+            hud::draw_bar(canvas, area, 1.0, from_right, background);
+            hud::draw_bar(canvas, area, health, from_right, Color::RGB(255, 0, 0));
+        }
+
+        for (player, area, from_right) in bars(meter_y, meter_height) {
+            let meter = f64::from(player.meter) / MAX_METER as f64;
+            hud::draw_bar(canvas, area, 1.0, from_right, background);
+            hud::draw_bar(canvas, area, meter, from_right, Color::RGB(0, 127, 255));
+        }
+
+        for (player, area, from_right) in bars(guard_y, guard_height) {
+            let guard = f64::from(player.guard) / MAX_GUARD as f64;
+            hud::draw_bar(canvas, area, guard, from_right, Color::RGB(255, 220, 120));
+        }
+
+        if let Some((side, counter, frame)) = self.counter_indicator {
+            if self.current_frame.since_with_freeze(frame) < 60 {
+                let x = match side {
+                    PlayerSide::Player1 => p1hx,
+                    PlayerSide::Player2 => p2hx,
+                };
+                let y = meter_y + meter_height as i32 * 3;
+
+                canvas.set_draw_color(match counter {
+                    CounterKind::CounterHit => Color::RGB(255, 200, 0),
+                    CounterKind::PunishCounter => Color::RGB(255, 60, 200),
+                });
+                canvas
+                    .fill_rect(sdl2::rect::Rect::new(
+                        x + self.camera.offset.x as i32,
+                        y + self.camera.offset.y as i32,
+                        health_width as u32 / 4,
+                        meter_height,
+                    ))
+                    .unwrap();
+            }
+        }
 
-        canvas.present();
+        for (side, x) in [
+            (PlayerSide::Player1, p1hx),
+            (PlayerSide::Player2, p2hx),
+        ] {
+            // The combo counter is shown on the side of the player doing the combo
+            let combo = &self.player(side.reverse()).combo;
+            let shown = combo.hits >= 2
+                && combo
+                    .ended
+                    .is_none_or(|ended| self.current_frame.since_with_freeze(ended) < 60);
+            if !shown {
+                continue;
+            }
+
+            let x = x + self.camera.offset.x as i32;
+            let y = meter_y + meter_height as i32 * 5 + self.camera.offset.y as i32;
+            let digit_height = hp_height * 3;
+
+            hud::draw_number(canvas, x, y, digit_height, combo.hits, Color::RGB(255, 255, 0));
+            hud::draw_number(
+                canvas,
+                x,
+                y + digit_height as i32 + hp_height as i32,
+                hp_height * 2,
+                i32::from(combo.damage) as usize,
+                Color::RGB(255, 255, 255),
+            );
+        }
     }
 }
 
@@ -543,10 +1054,9 @@ pub struct Hit {
     attacker: PlayerSide,
     target: PlayerSide,
     attacker_tag: String,
-    target_tag: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, ValueEnum)]
 pub enum PlayerSide {
     Player1,
     Player2,
@@ -576,6 +1086,11 @@ impl FromStr for PlayerSide {
 #[derive(Clone)]
 pub struct Player {
     health: FixedPoint,
+    meter: FixedPoint,
+    guard: FixedPoint,
+    /// Frames left until the guard gauge starts regenerating
+    guard_regen_delay: usize,
+    combo: Combo,
     position: Vec2,
     velocity: Vec2,
     character: Character,
@@ -589,9 +1104,74 @@ pub struct Player {
     current_attack: Option<Attack>,
     facing: Facing,
     grounded: bool,
+    /// Can't be hit while set
+    invincible: bool,
+    air_jumps_left: usize,
+    air_dash_used: bool,
+    wall_bounce: Option<FixedPoint>,
+    ground_bounce: Option<FixedPoint>,
+    palette: usize,
+}
+
+impl Hash for Player {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.health.hash(state);
+        self.meter.hash(state);
+        self.guard.hash(state);
+        self.guard_regen_delay.hash(state);
+        self.combo.hash(state);
+        self.position.hash(state);
+        self.velocity.hash(state);
+        self.character.hash(state);
+        self.input_history.hash(state);
+        self.animator.hash(state);
+        self.current_state.hash(state);
+        // The rest of the attack comes from the move, only the hits landed so far change
+        self.current_attack
+            .as_ref()
+            .map(|attack| (attack.hits, attack.last_hit))
+            .hash(state);
+        self.facing.hash(state);
+        self.grounded.hash(state);
+        self.invincible.hash(state);
+        self.air_jumps_left.hash(state);
+        self.air_dash_used.hash(state);
+        self.wall_bounce.hash(state);
+        self.ground_bounce.hash(state);
+    }
 }
 
 impl Player {
+    fn new(game_info: &GameInfo, choice: CharacterChoice, x: FixedPoint, facing: Facing) -> Self {
+        let proto = &game_info.character_protos[&choice.character];
+        Self {
+            health: proto.stats.health,
+            meter: FixedPoint::ZERO,
+            guard: FixedPoint::from(MAX_GUARD),
+            guard_regen_delay: 0,
+            combo: Combo::new(),
+            position: Vec2::new(x, FixedPoint::ZERO),
+            velocity: Vec2::new(FixedPoint::from(0), FixedPoint::from(0)),
+            character: choice.character,
+            last_input: BoxedInput::new(),
+            input_history: InputHistory::new(),
+            animator: Animator::new(Frame::new(), proto.animations["idle"]),
+            current_state: IdleState.wrap(),
+            state_transition_requests: StateTransitionRequests::new(),
+            hurtboxes: vec![],
+            hitboxes: vec![],
+            current_attack: None,
+            facing,
+            grounded: true,
+            invincible: false,
+            air_jumps_left: proto.stats.air_jumps,
+            air_dash_used: false,
+            wall_bounce: None,
+            ground_bounce: None,
+            palette: choice.palette,
+        }
+    }
+
     fn set_facing(&mut self, new_facing: Facing) {
         if self.facing == new_facing {
             return;
@@ -608,6 +1188,31 @@ impl Player {
         }
     }
 
+    fn gain_meter(&mut self, amount: FixedPoint) {
+        let max = FixedPoint::from(MAX_METER);
+        self.meter += amount;
+        if self.meter > max {
+            self.meter = max;
+        }
+    }
+
+    fn regenerate_guard(&mut self) {
+        if matches!(self.current_state, CharacterState::GuardCrush(_)) {
+            return;
+        }
+
+        if self.guard_regen_delay > 0 {
+            self.guard_regen_delay -= 1;
+            return;
+        }
+
+        let max = FixedPoint::from(MAX_GUARD);
+        self.guard += FixedPoint::from(0.5);
+        if self.guard > max {
+            self.guard = max;
+        }
+    }
+
     fn input(&mut self, frame: Frame, input: BoxedInput) {
         let last_input = self.last_input;
 
@@ -653,41 +1258,14 @@ impl Player {
     }
 
     pub fn render(&self, camera: &Camera, game_info: &GameInfo, canvas: &mut Canvas<Window>) {
-        let flip = self.facing == Facing::Left;
-
-        let anim = self.animator.current_animation;
-        let texture = &anim.texture_atlas;
-
-        let pos = self.position;
-
-        let mut origin = anim.frame_data[self.animator.current_frame].origin;
-        if flip {
-            origin.x = FixedPoint::from(anim.cell_width) - origin.x;
-        }
-
-        let pos = pos - origin;
-
-        let screen_pos = camera.to_screen_space(pos);
-
-        let index = self.animator.current_frame;
-        let cell_y = index / anim.columns;
-        let cell_x = index % anim.columns;
-
-        let src = sdl2::rect::Rect::new(
-            (cell_x * anim.cell_width) as _,
-            (cell_y * anim.cell_height) as _,
-            anim.cell_width as _,
-            anim.cell_height as _,
-        );
-        let w = anim.cell_width as f32 * camera.scale;
-        let h = anim.cell_height as f32 * camera.scale;
-        let dst = sdl2::rect::Rect::new(screen_pos.x as _, screen_pos.y as _, w as _, h as _);
-
-        // let src = None;
-        // let dst = None;
-        canvas
-            .copy_ex(texture, src, dst, 0.0, None, flip, false)
-            .unwrap();
+        self.animator.render(
+            &game_info.animations,
+            self.position,
+            self.facing,
+            self.palette,
+            camera,
+            canvas,
+        );
 
         // for hurtbox in &self.hurtboxes {
         //     let mut min = hurtbox.rect.min;
@@ -758,6 +1336,13 @@ impl Player {
         // }
     }
 
+    pub fn is_in_hitstun(&self) -> bool {
+        matches!(
+            &self.current_state,
+            CharacterState::Hitstun(_) | CharacterState::Crumple(_) | CharacterState::GuardCrush(_)
+        )
+    }
+
     pub fn is_blocking(&self, frame: Frame) -> bool {
         if matches!(&self.current_state, CharacterState::Blockstun(_)) {
             return true;
@@ -776,38 +1361,166 @@ impl Player {
         false
     }
 
-    pub fn apply_effect(&mut self, effect: &Effect) {
+    /// Can't be hit, either because of the state or the current animation
+    pub fn is_invincible(&self) -> bool {
+        self.invincible || self.animator.invincible
+    }
+
+    /// Whether a hit right now would be a counter hit, based on how far into their move the
+    /// player is, unless the animation overrides it
+    pub fn counter_kind(&self, animations: &AnimationStore, frame: Frame) -> Option<CounterKind> {
+        match self.animator.counter_state {
+            CounterState::Auto => {}
+            CounterState::NoCounter => return None,
+            CounterState::CounterHit => return Some(CounterKind::CounterHit),
+            CounterState::PunishCounter => return Some(CounterKind::PunishCounter),
+        }
+        if !matches!(self.current_state, CharacterState::CharacterSpecific(_)) {
+            return None;
+        }
+
+        let elapsed = self.animator.elapsed(animations, frame);
+        match animations[self.animator.current_animation].phase(elapsed) {
+            AttackPhase::Startup | AttackPhase::Active => Some(CounterKind::CounterHit),
+            AttackPhase::Recovery => Some(CounterKind::PunishCounter),
+        }
+    }
+
+    /// Only called by `GameState::apply_effect`, which handles the effects that need more than
+    /// the player themselves
+    fn apply_effect(&mut self, effect: &Effect, stats: &CharacterStats) {
         match effect {
-            Effect::Damage(amt) => self.health -= *amt,
-            Effect::Hitstun(amt) => self.state_transition_requests.add(StateTransitionRequest {
-                state: HitstunState(*amt).wrap(),
-                insert_priority: usize::MAX,
-            }),
-            Effect::Blockstun(amt) => self.state_transition_requests.add(StateTransitionRequest {
-                state: BlockstunState(*amt).wrap(),
-                insert_priority: usize::MAX,
-            }),
+            Effect::Damage(amt) => {
+                self.health -= *amt;
+                if self.combo.is_active() {
+                    self.combo.damage += *amt;
+                }
+            }
+            Effect::Hitstun(amt) => self
+                .state_transition_requests
+                .add(StateTransitionRequest::new(HitstunState(*amt).wrap(), usize::MAX)),
+            Effect::Blockstun(amt) => self
+                .state_transition_requests
+                .add(StateTransitionRequest::new(BlockstunState(*amt).wrap(), usize::MAX)),
             Effect::Knockback(amount) => {
+                let amount = *amount / stats.weight;
                 self.velocity.x = match self.facing {
-                    Facing::Left => *amount,
-                    Facing::Right => -*amount,
+                    Facing::Left => amount,
+                    Facing::Right => -amount,
                 };
             }
-            Effect::StateChange(_) => todo!(),
+            Effect::PullIn(amount) => {
+                self.velocity.x = match self.facing {
+                    Facing::Left => -*amount,
+                    Facing::Right => *amount,
+                };
+            }
+            Effect::WallBounce(amount) => self.wall_bounce = Some(*amount),
+            Effect::GroundBounce(amount) => {
+                if self.grounded {
+                    // Already on the ground, so bounce right away
+                    self.velocity.y = -*amount;
+                    self.grounded = false;
+                } else {
+                    self.ground_bounce = Some(*amount);
+                }
+            }
+            Effect::StateChange(state) => {
+                self.state_transition_requests
+                    .add(StateTransitionRequest::new(state.clone(), usize::MAX))
+            }
+            Effect::GuardDamage(amt) => {
+                self.guard -= *amt;
+                self.guard_regen_delay = GUARD_REGEN_DELAY;
+                if self.guard <= FixedPoint::ZERO {
+                    self.guard = FixedPoint::ZERO;
+                    self.state_transition_requests.add(StateTransitionRequest::new(
+                        GuardCrushState(GUARD_CRUSH_STUN).wrap(),
+                        usize::MAX,
+                    ));
+                }
+            }
+            // Handled by `GameState::apply_effect` before it gets here
+            Effect::ChipDamage(_) => unreachable!(),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct Attack {
-    has_hit_player: bool,
+    /// Used when no hitbox group matches the hit
+    hit_effect: HitEffect,
+    /// Checked in order, so earlier groups take precedence (e.g. sweet spots before sour spots)
+    hitbox_groups: Vec<HitboxGroup>,
+    max_hits: usize,
+    /// Minimum amount of frames between two hits of the same attack
+    rehit_interval: usize,
+    hits: usize,
+    last_hit: Option<Frame>,
+}
+
+/// Hit effect used when a hitbox with the given tag lands the hit
+#[derive(Clone)]
+pub struct HitboxGroup {
+    attacker_tag: String,
     hit_effect: HitEffect,
 }
 
+impl Attack {
+    pub fn new(hit_effect: HitEffect) -> Self {
+        Self {
+            hit_effect,
+            hitbox_groups: vec![],
+            max_hits: 1,
+            rehit_interval: 1,
+            hits: 0,
+            last_hit: None,
+        }
+    }
+
+    pub fn with_hitbox_group(mut self, attacker_tag: &str, hit_effect: HitEffect) -> Self {
+        self.hitbox_groups.push(HitboxGroup {
+            attacker_tag: attacker_tag.into(),
+            hit_effect,
+        });
+        self
+    }
+
+    pub fn with_rehit(mut self, max_hits: usize, rehit_interval: usize) -> Self {
+        self.max_hits = max_hits;
+        self.rehit_interval = rehit_interval.max(1);
+        self
+    }
+
+    pub fn can_hit(&self, frame: Frame) -> bool {
+        self.hits < self.max_hits
+            && self
+                .last_hit
+                .is_none_or(|last| frame.since_without_freeze(last) >= self.rehit_interval)
+    }
+
+    pub fn register_hit(&mut self, frame: Frame) {
+        self.hits += 1;
+        self.last_hit = Some(frame);
+    }
+
+    fn tagged_hit_effect(&self, attacker_tag: &str) -> Option<(usize, &HitEffect)> {
+        self.hitbox_groups
+            .iter()
+            .enumerate()
+            .find(|(_, group)| group.attacker_tag == attacker_tag)
+            .map(|(index, group)| (index, &group.hit_effect))
+    }
+
+    pub fn hit_effect(&self, attacker_tag: &str) -> &HitEffect {
+        self.tagged_hit_effect(attacker_tag)
+            .map_or(&self.hit_effect, |(_, hit_effect)| hit_effect)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Hurtbox {
     rect: Rect,
-    tag: String,
 }
 
 #[derive(Debug, Clone)]
@@ -816,54 +1529,172 @@ pub struct Hitbox {
     tag: String,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
 pub struct Animator {
-    current_animation: &'static Animation,
+    current_animation: AnimationId,
     current_frame: usize,
     last_change_frame: Frame,
     state_after_animation: Option<CharacterState>,
+    /// Animations to play once the current one is over, instead of following its loop mode
+    queue: VecDeque<AnimationId>,
     update_hitboxes: bool,
+    /// Set when a new frame has been entered whose root motion and events haven't been handled
+    frame_entered: bool,
+    /// Root motion is an offset from the start of the animation, so this is the part of it
+    /// that has already been applied
+    applied_root_motion: Vec2,
+    /// Set by the frame events of the current animation
+    invincible: bool,
+    cancel_window: bool,
+    counter_state: CounterState,
+}
+
+/// What happened in the animation during a tick
+pub struct AnimatorTick {
+    /// Movement since the last frame of the animation, flipped according to the facing
+    pub root_motion: Vec2,
+    /// Events of the frame that was entered that the animator doesn't handle itself
+    pub events: Vec<FrameEvent>,
 }
 
 impl Animator {
-    pub fn new(current_frame: Frame, initial_animation: &'static Animation) -> Self {
+    pub fn new(current_frame: Frame, initial_animation: AnimationId) -> Self {
         Self {
             current_animation: initial_animation,
             current_frame: 0,
             last_change_frame: current_frame,
             state_after_animation: None,
+            queue: VecDeque::new(),
             update_hitboxes: true,
+            frame_entered: true,
+            applied_root_motion: Vec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            invincible: false,
+            cancel_window: false,
+            counter_state: CounterState::Auto,
         }
     }
 
     pub fn switch_animation(
         &mut self,
         frame: Frame,
-        new_animation: &'static Animation,
+        new_animation: AnimationId,
         state_after: Option<CharacterState>,
     ) {
-        self.current_animation = new_animation;
-        self.current_frame = 0;
-        self.last_change_frame = frame;
+        self.start(frame, new_animation);
         self.state_after_animation = state_after;
+        self.queue.clear();
+    }
+
+    /// Plays the animation once the current one and any queued before it are over. The state
+    /// after the animation is only transitioned to once the whole queue has played.
+    pub fn queue_animation(&mut self, animation: AnimationId) {
+        self.queue.push_back(animation);
+    }
+
+    fn start(&mut self, frame: Frame, animation: AnimationId) {
+        self.current_animation = animation;
+        self.enter_frame(frame, 0);
+        self.applied_root_motion = Vec2::new(FixedPoint::ZERO, FixedPoint::ZERO);
+        self.invincible = false;
+        self.cancel_window = false;
+        self.counter_state = CounterState::Auto;
+    }
+
+    fn enter_frame(&mut self, frame: Frame, index: usize) {
+        self.current_frame = index;
+        self.last_change_frame = frame;
         self.update_hitboxes = true;
+        self.frame_entered = true;
     }
 
     pub fn make_sure_animation(
         &mut self,
         frame: Frame,
-        animation: &'static Animation,
+        animation: AnimationId,
         state_after: Option<CharacterState>,
     ) {
-        if !std::ptr::eq(self.current_animation, animation) {
+        if self.current_animation != animation {
             self.switch_animation(frame, animation, state_after);
         }
     }
 
-    pub fn pre_tick(&mut self, current_frame: Frame) -> Option<StateTransitionRequest> {
-        if self.current_frame + 1 >= self.current_animation.frame_data.len()
+    pub fn render(
+        &self,
+        animations: &AnimationStore,
+        position: Vec2,
+        facing: Facing,
+        palette: usize,
+        camera: &Camera,
+        canvas: &mut Canvas<Window>,
+    ) {
+        let flip = facing == Facing::Left;
+
+        let anim = &animations[self.current_animation];
+        let frame = &anim.frame_data[self.current_frame];
+        let sprite = &frame.sprite;
+        if sprite.is_empty() {
+            return;
+        }
+        let texture = animations.texture(anim, sprite, palette);
+
+        let mut origin = frame.origin;
+        if flip {
+            origin.x = FixedPoint::from(sprite.width as usize) - origin.x;
+        }
+
+        let pos = position - origin;
+
+        let screen_pos = camera.to_screen_space(pos);
+
+        let src = sdl2::rect::Rect::new(sprite.x as _, sprite.y as _, sprite.width, sprite.height);
+        let w = sprite.width as f32 * camera.scale;
+        let h = sprite.height as f32 * camera.scale;
+        let dst = sdl2::rect::Rect::new(screen_pos.x as _, screen_pos.y as _, w as _, h as _);
+
+        canvas
+            .copy_ex(texture, src, dst, 0.0, None, flip, false)
+            .unwrap();
+    }
+
+    /// Moves back to the last frame if the animation got shorter when it was reloaded
+    pub fn clamp_frame(&mut self, animations: &AnimationStore) {
+        let last_frame = animations[self.current_animation].frame_data.len() - 1;
+        if self.current_frame > last_frame {
+            self.current_frame = last_frame;
+            self.update_hitboxes = true;
+        }
+    }
+
+    /// Frames since the start of the current animation
+    pub fn elapsed(&self, animations: &AnimationStore, current_frame: Frame) -> usize {
+        animations[self.current_animation].frame_data[..self.current_frame]
+            .iter()
+            .map(|fd| fd.delay)
+            .sum::<usize>()
+            + current_frame.since_without_freeze(self.last_change_frame)
+    }
+
+    /// How far into the current animation the animator is, from 0 to 1. Stays at 1 while
+    /// holding the last frame.
+    pub fn progress(&self, animations: &AnimationStore, current_frame: Frame) -> FixedPoint {
+        let duration = animations[self.current_animation].duration();
+        if duration == 0 {
+            return FixedPoint::ONE;
+        }
+        let elapsed = self.elapsed(animations, current_frame).min(duration);
+        FixedPoint::from(elapsed) / FixedPoint::from(duration)
+    }
+
+    pub fn pre_tick(
+        &mut self,
+        animations: &AnimationStore,
+        current_frame: Frame,
+    ) -> Option<StateTransitionRequest> {
+        let animation = &animations[self.current_animation];
+        if self.queue.is_empty()
+            && self.current_frame + 1 >= animation.frame_data.len()
             && current_frame.since_without_freeze(self.last_change_frame)
-                >= self.current_animation.frame_data[self.current_frame].delay
+                >= animation.frame_data[self.current_frame].delay
         {
             self.state_after_animation
                 .take()
@@ -873,30 +1704,67 @@ impl Animator {
         }
     }
 
+    /// Advances the animation and updates the boxes. If a frame was entered this tick, its events
+    /// are handled and its root motion is returned.
     pub fn tick(
         &mut self,
+        animations: &AnimationStore,
         facing: Facing,
         hitboxes: &mut Vec<Hitbox>,
         hurtboxes: &mut Vec<Hurtbox>,
         current_frame: Frame,
-    ) {
+    ) -> AnimatorTick {
+        let mut animation = &animations[self.current_animation];
         if current_frame.since_without_freeze(self.last_change_frame)
-            >= self.current_animation.frame_data[self.current_frame].delay
+            >= animation.frame_data[self.current_frame].delay
         {
-            self.last_change_frame = current_frame;
-            self.current_frame += 1;
-            if self.current_frame >= self.current_animation.frame_data.len() {
-                self.current_frame = 0;
+            if self.current_frame + 1 < animation.frame_data.len() {
+                self.enter_frame(current_frame, self.current_frame + 1);
+            } else if let Some(next) = self.queue.pop_front() {
+                self.start(current_frame, next);
+                animation = &animations[next];
+            } else {
+                let loop_start = match animation.loop_mode {
+                    LoopMode::Loop => Some(0),
+                    LoopMode::LoopFrom(frame) => Some(frame),
+                    LoopMode::Hold => None,
+                };
+                if let Some(frame) = loop_start {
+                    self.enter_frame(current_frame, frame);
+                    // Carry on from where the last loop left off instead of going back
+                    self.applied_root_motion = animation.frame_data[frame].root_motion;
+                }
             }
+        }
 
-            self.update_hitboxes = true;
+        let mut result = AnimatorTick {
+            root_motion: Vec2::new(FixedPoint::ZERO, FixedPoint::ZERO),
+            events: vec![],
+        };
+        if self.frame_entered {
+            self.frame_entered = false;
+            let frame = &animation.frame_data[self.current_frame];
+            result.root_motion = frame.root_motion - self.applied_root_motion;
+            self.applied_root_motion = frame.root_motion;
+            if facing == Facing::Left {
+                result.root_motion.x = -result.root_motion.x;
+            }
+
+            for event in &frame.events {
+                match event {
+                    FrameEvent::SetInvincible(invincible) => self.invincible = *invincible,
+                    FrameEvent::CancelWindow(open) => self.cancel_window = *open,
+                    FrameEvent::CounterState(state) => self.counter_state = *state,
+                    _ => result.events.push(event.clone()),
+                }
+            }
         }
 
         if self.update_hitboxes {
             hitboxes.clear();
             hurtboxes.clear();
 
-            let cur_frame = &self.current_animation.frame_data[self.current_frame];
+            let cur_frame = &animation.frame_data[self.current_frame];
             let flip = facing == Facing::Left;
 
             for (id, pos) in &cur_frame.hitboxes {
@@ -911,12 +1779,9 @@ impl Animator {
                     rect.min.x = -max_x;
                     rect.max.x = -min_x;
                 }
-                let hitbox_info = &self.current_animation.hitboxes[id];
+                let hitbox_info = &animation.hitboxes[id];
                 if hitbox_info.is_hurtbox {
-                    hurtboxes.push(Hurtbox {
-                        rect,
-                        tag: hitbox_info.tag.clone(),
-                    })
+                    hurtboxes.push(Hurtbox { rect })
                 } else {
                     hitboxes.push(Hitbox {
                         rect,
@@ -925,6 +1790,8 @@ impl Animator {
                 }
             }
         }
+
+        result
     }
 }
 
@@ -936,6 +1803,8 @@ pub struct GameRunner {
     session: P2PSession<GGRSConfig>,
     skip_frames: u32,
     waiting_for_network: bool,
+    show_event_log: bool,
+    scene_target: SceneTarget,
 }
 
 struct GGRSConfig;
@@ -999,40 +1868,81 @@ impl GameRunner {
             .start_p2p_session(socket)
             .unwrap();
 
+        // Left over from the previous match otherwise
+        info.event_log.borrow_mut().rewind(state.current_frame);
+
         Self {
             info,
             state,
             session,
             skip_frames: 0,
             waiting_for_network: false,
+            show_event_log: false,
+            scene_target: SceneTarget::new(),
         }
     }
 
     pub fn input(&mut self, input: impl Into<Input>, pressed: bool) {
-        let input = input.into();
+        self.info.input(input, pressed);
+    }
+}
 
-        let players = [&mut self.info.player_1, &mut self.info.player_2];
+impl crate::state::State for GameRunner {
+    fn on_enter(&mut self, ctx: &mut crate::app_context::AppContext) {}
 
-        for player in players {
-            match player {
-                PlayerType::Local {
-                    mapping,
-                    input: boxed_input,
-                } => {
-                    if let Some(action) = mapping.get_action(input) {
-                        if pressed {
-                            boxed_input.press(action);
-                        } else {
-                            boxed_input.release(action);
-                        }
-                    }
+    fn on_suspend(&mut self, ctx: &mut crate::app_context::AppContext) {}
+
+    fn on_resume(&mut self, ctx: &mut crate::app_context::AppContext) {}
+
+    fn on_exit(&mut self, ctx: &mut crate::app_context::AppContext) {}
+
+    fn event(&mut self, event: sdl2::event::Event, ctx: &mut crate::app_context::AppContext) {
+        match event {
+            Event::KeyDown {
+                scancode: Some(Scancode::F1),
+                repeat: false,
+                ..
+            } => self.show_event_log = !self.show_event_log,
+            Event::KeyDown {
+                scancode: Some(Scancode::F4),
+                repeat: false,
+                ..
+            } => {
+                self.info.render_mode = self.info.render_mode.next();
+            }
+            Event::KeyDown {
+                scancode: Some(Scancode::F3),
+                repeat: false,
+                ..
+            } => {
+                let path = format!("event_log_{}.txt", self.state.current_frame.number());
+                match self.info.event_log.borrow().export(&path) {
+                    Ok(()) => println!("Exported event log to {path}"),
+                    Err(e) => println!("Couldn't export event log: {e}"),
                 }
-                PlayerType::Remote => {}
             }
+            Event::KeyDown {
+                scancode: Some(scancode),
+                ..
+            } => self.input(scancode, true),
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => self.input(scancode, false),
+            Event::ControllerButtonDown { button, .. } => self.input(button, true),
+            Event::ControllerButtonUp { button, .. } => self.input(button, false),
+            _ => {}
         }
     }
 
-    pub fn tick(&mut self, canvas: &mut Canvas<Window>) {
+    fn tick(&mut self, ctx: &mut crate::app_context::AppContext) -> StateTransition {
+        if let Some(mut hot_reload) = self.info.hot_reload.take() {
+            if hot_reload.poll(&mut self.info) {
+                self.state.reloaded(&self.info);
+            }
+            self.info.hot_reload = Some(hot_reload);
+        }
+
         for (handle, player) in [&self.info.player_1, &self.info.player_2]
             .into_iter()
             .enumerate()
@@ -1091,7 +2001,7 @@ impl GameRunner {
 
         if self.skip_frames > 0 {
             self.skip_frames -= 1;
-            return;
+            return StateTransition::None;
         }
         self.info.waiting_for_network = self.waiting_for_network;
 
@@ -1113,11 +2023,15 @@ impl GameRunner {
                     match req {
                         ggrs::GGRSRequest::SaveGameState { cell, frame } => {
                             // println!("Save");
-                            cell.save(frame, Some(self.state.clone()), Some({ 0 }))
+                            cell.save(frame, Some(self.state.clone()), Some(self.state.checksum()))
                         }
                         ggrs::GGRSRequest::LoadGameState { cell, frame } => {
                             // println!("Load");
-                            self.state = cell.load().unwrap()
+                            self.state = cell.load().unwrap();
+                            self.info
+                                .event_log
+                                .borrow_mut()
+                                .rewind(self.state.current_frame);
                         }
                         ggrs::GGRSRequest::AdvanceFrame { inputs } => {
                             // println!("Advance");
@@ -1138,36 +2052,808 @@ impl GameRunner {
         } else {
             self.session.poll_remote_clients();
         }
-        self.state.render(&self.info, canvas);
+
+        StateTransition::None
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, ctx: &mut crate::app_context::AppContext) {
+        self.state.render(&self.info, &mut self.scene_target, canvas);
+        if self.show_event_log {
+            self.info
+                .event_log
+                .borrow()
+                .render(self.state.current_frame, canvas);
+        }
     }
 }
 
-impl crate::state::State for GameRunner {
-    fn on_enter(&mut self, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+#[cfg(test)]
+mod tests {
+    use super::{
+        animation::AnimationErrorKind,
+        character::{AirborneState, DashDirection, JumpDirection, JumpKind},
+        *,
+    };
+
+    /// Guy against Guy, without a window
+    fn start() -> (GameInfo, GameState) {
+        let info = GameInfo::create(None).unwrap();
+        let guy = CharacterChoice {
+            character: Character::Guy,
+            palette: 0,
+        };
+        let state = GameState::new(&info, guy, guy);
+        (info, state)
     }
 
-    fn on_suspend(&mut self, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+    /// Simulates a frame with player 1 holding `actions` and player 2 not touching anything
+    fn tick(info: &GameInfo, state: &mut GameState, actions: &[Action]) {
+        tick_both(info, state, actions, &[]);
     }
 
-    fn on_resume(&mut self, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+    /// Simulates a frame with each player holding their actions
+    fn tick_both(
+        info: &GameInfo,
+        state: &mut GameState,
+        p1_actions: &[Action],
+        p2_actions: &[Action],
+    ) {
+        for (side, actions) in [
+            (PlayerSide::Player1, p1_actions),
+            (PlayerSide::Player2, p2_actions),
+        ] {
+            let mut input = BoxedInput::new();
+            for &action in actions {
+                input.press(action);
+            }
+            state.player_input(side, input);
+        }
+        state.tick(info);
     }
 
-    fn on_exit(&mut self, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+    #[test]
+    fn logs_moves_and_the_transitions_they_cause() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[]);
+        tick(&info, &mut state, &[Action::Punch]);
+
+        let log = info.event_log.borrow();
+        let events: Vec<_> = log
+            .for_player(PlayerSide::Player1)
+            .filter(|logged| logged.frame.number() == 1)
+            .map(|logged| &logged.event)
+            .collect();
+        assert!(matches!(
+            events[..],
+            [
+                GameEvent::MovePerformed { name, .. },
+                GameEvent::StateTransition {
+                    from: CharacterState::Idle(_),
+                    to: CharacterState::CharacterSpecific(_),
+                    ..
+                },
+                ..
+            ] if name == "Punch"
+        ));
+        assert!(log
+            .for_player(PlayerSide::Player2)
+            .all(|logged| !matches!(logged.event, GameEvent::MovePerformed { .. })));
     }
 
-    fn event(&mut self, event: sdl2::event::Event, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+    #[test]
+    fn logs_rejected_requests() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[Action::Punch]);
+        state
+            .player_1
+            .state_transition_requests
+            .add(StateTransitionRequest::new(IdleState.wrap(), 0));
+        tick(&info, &mut state, &[Action::Punch]);
+
+        let log = info.event_log.borrow();
+        let rejected = log
+            .iter()
+            .find_map(|logged| match &logged.event {
+                GameEvent::TransitionRejected {
+                    player,
+                    requested,
+                    priority,
+                    requester,
+                    ..
+                } => Some((
+                    logged.frame.number(),
+                    *player,
+                    requested,
+                    *priority,
+                    requester,
+                )),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(rejected.0, 1);
+        assert_eq!(rejected.1, PlayerSide::Player1);
+        assert!(matches!(rejected.2, CharacterState::Idle(_)));
+        assert_eq!(rejected.3, 0);
+        assert_eq!(rejected.4.file(), file!());
     }
 
-    fn tick(&mut self, ctx: &mut crate::app_context::AppContext) -> StateTransition {
-        todo!()
+    #[test]
+    fn logs_hits() {
+        let (info, mut state) = start();
+        state.player_2.position.x = state.player_1.position.x + FixedPoint::from(20.0);
+        for _ in 0..20 {
+            tick(&info, &mut state, &[Action::Punch]);
+        }
+
+        let log = info.event_log.borrow();
+        let hits: Vec<_> = log
+            .iter()
+            .filter(|logged| matches!(logged.event, GameEvent::Hit { .. }))
+            .collect();
+        assert_eq!(hits.len(), 1);
+        assert!(matches!(
+            hits[0].event,
+            GameEvent::Hit {
+                attacker: PlayerSide::Player1,
+                target: PlayerSide::Player2,
+                blocked: false,
+                projectile: false,
+                ..
+            }
+        ));
+        assert!(log.for_player(PlayerSide::Player2).any(|logged| matches!(
+            logged.event,
+            GameEvent::EffectApplied {
+                effect: Effect::Damage(_),
+                ..
+            }
+        )));
     }
 
-    fn render(&mut self, canvas: &mut Canvas<Window>, ctx: &mut crate::app_context::AppContext) {
-        todo!()
+    /// Hit effect that only does the given damage
+    fn damage(amount: usize) -> HitEffect {
+        HitEffect {
+            unblockable: false,
+            effects_on_hit: vec![Effect::Damage(amount.into())],
+            effects_on_block: vec![],
+            self_effects_on_hit: vec![],
+            self_effects_on_block: vec![],
+            meter_gain_on_hit: FixedPoint::ZERO,
+            meter_gain_on_block: FixedPoint::ZERO,
+            counter_hit: CounterModifiers::NONE,
+            punish_counter: CounterModifiers::NONE,
+            starter_scaling: FixedPoint::ONE,
+        }
+    }
+
+    fn damage_of(hit_effect: &HitEffect) -> FixedPoint {
+        match hit_effect.effects_on_hit[..] {
+            [Effect::Damage(amount)] => amount,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn earlier_hitbox_groups_take_precedence() {
+        let attack = Attack::new(damage(1))
+            .with_hitbox_group("Forearm", damage(2))
+            .with_hitbox_group("Fist", damage(3))
+            .with_hitbox_group("Fist", damage(4));
+
+        let index = |attacker_tag| {
+            attack
+                .tagged_hit_effect(attacker_tag)
+                .map(|(index, _)| index)
+        };
+        assert_eq!(index("Forearm"), Some(0));
+        assert_eq!(index("Fist"), Some(1));
+        assert_eq!(index("Elbow"), None);
+
+        assert_eq!(damage_of(attack.hit_effect("Forearm")), 2.into());
+        assert_eq!(damage_of(attack.hit_effect("Fist")), 3.into());
+        assert_eq!(damage_of(attack.hit_effect("Elbow")), 1.into());
+    }
+
+    #[test]
+    fn rehits_wait_for_the_interval() {
+        let mut attack = Attack::new(damage(1)).with_rehit(2, 3);
+        let mut frame = Frame::new();
+        assert!(attack.can_hit(frame));
+        attack.register_hit(frame);
+
+        for _ in 0..2 {
+            frame.tick(false);
+            assert!(!attack.can_hit(frame));
+        }
+        // Frozen frames don't count towards the interval
+        frame.tick(true);
+        assert!(!attack.can_hit(frame));
+        frame.tick(false);
+        assert!(attack.can_hit(frame));
+        attack.register_hit(frame);
+
+        for _ in 0..10 {
+            frame.tick(false);
+            assert!(!attack.can_hit(frame), "only hits twice");
+        }
+    }
+
+    #[test]
+    fn big_punch_hits_twice() {
+        let (info, mut state) = start();
+        state.player_2.position.x = state.player_1.position.x + FixedPoint::from(25.0);
+        state.player_1.meter = MAX_METER.into();
+        tick(&info, &mut state, &[]);
+        for _ in 0..100 {
+            tick(&info, &mut state, &[Action::Punch, Action::Kick]);
+        }
+
+        let log = info.event_log.borrow();
+        assert!(log.iter().any(|logged| matches!(
+            &logged.event,
+            GameEvent::MovePerformed { name, .. } if name == "Big Punch"
+        )));
+        let hits = log
+            .iter()
+            .filter(|logged| matches!(logged.event, GameEvent::Hit { .. }))
+            .count();
+        assert_eq!(hits, 2);
+    }
+
+    #[test]
+    fn checksum_covers_state_that_changes_how_the_match_plays_out() {
+        let (_info, state) = start();
+        let changes: [fn(&mut Player); 4] = [
+            |player| player.guard_regen_delay = 10,
+            |player| player.combo.hits = 2,
+            |player| player.wall_bounce = Some(FixedPoint::ONE),
+            |player| player.ground_bounce = Some(FixedPoint::ONE),
+        ];
+        for change in changes {
+            let mut changed = state.clone();
+            change(changed.player_mut(PlayerSide::Player1));
+            assert_ne!(changed.checksum(), state.checksum());
+        }
+    }
+
+    #[test]
+    fn frame_events_can_only_spawn_what_the_character_has() {
+        let mut info = GameInfo::create(None).unwrap();
+        let guy = info.character_protos.get_mut(&Character::Guy).unwrap();
+        let throw = &info.animations[guy.animations["fireball_throw"]];
+        assert!(guy.check_frame_events(throw).is_ok());
+
+        guy.projectiles.clear();
+        assert!(matches!(
+            guy.check_frame_events(throw),
+            Err(AnimationErrorKind::UnknownProjectile { projectile, .. }) if projectile == "fireball"
+        ));
+    }
+
+    #[test]
+    fn animator_handles_frame_events_once_per_frame_entered() {
+        let mut animations = AnimationStore::new();
+        let animation = animations.add_test_animation(
+            vec![
+                (2, vec![FrameEvent::SpawnProjectile("fireball".into())]),
+                (
+                    3,
+                    vec![
+                        FrameEvent::SetInvincible(true),
+                        FrameEvent::PlaySound("whoosh".into()),
+                    ],
+                ),
+                (1, vec![FrameEvent::SetInvincible(false)]),
+            ],
+            LoopMode::Hold,
+        );
+
+        let mut frame = Frame::new();
+        let mut animator = Animator::new(frame, animation);
+        let mut events = vec![];
+        let mut invincible = vec![];
+        for _ in 0..10 {
+            let tick = animator.tick(&animations, Facing::Right, &mut vec![], &mut vec![], frame);
+            events.push(tick.events);
+            invincible.push(animator.invincible);
+            frame.tick(false);
+        }
+
+        // Set events are applied by the animator, the others are for the game state
+        for (tick, events) in events.iter().enumerate() {
+            match tick {
+                0 => assert_eq!(events, &[FrameEvent::SpawnProjectile("fireball".into())]),
+                2 => assert_eq!(events, &[FrameEvent::PlaySound("whoosh".into())]),
+                _ => assert!(events.is_empty(), "tick {tick}: {events:?}"),
+            }
+        }
+        assert_eq!(
+            invincible,
+            [false, false, true, true, true, false, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn fireball_spawns_from_its_animation() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[]);
+        for _ in 0..30 {
+            tick(&info, &mut state, &[Action::Kick]);
+        }
+
+        let log = info.event_log.borrow();
+        let spawns: Vec<_> = log
+            .iter()
+            .filter(|logged| {
+                matches!(
+                    &logged.event,
+                    GameEvent::FrameEvent {
+                        player: PlayerSide::Player1,
+                        event: FrameEvent::SpawnProjectile(name),
+                    } if name == "fireball"
+                )
+            })
+            .map(|logged| logged.frame.number())
+            .collect();
+        // Pressed on frame 1, and the throw reaches its fifth frame four frames later
+        assert_eq!(spawns, [5]);
+    }
+
+    /// Runs the animator like `GameState::tick` does, returning the animation and frame it is
+    /// on after each tick, and whether it requested the state after the animation
+    fn play(
+        animations: &AnimationStore,
+        animator: &mut Animator,
+        ticks: usize,
+    ) -> Vec<(AnimationId, usize, bool)> {
+        let mut frame = animator.last_change_frame;
+        (0..ticks)
+            .map(|_| {
+                let request = animator.pre_tick(animations, frame).is_some();
+                animator.tick(animations, Facing::Right, &mut vec![], &mut vec![], frame);
+                frame.tick(false);
+                (animator.current_animation, animator.current_frame, request)
+            })
+            .collect()
+    }
+
+    fn test_animation(
+        animations: &mut AnimationStore,
+        delays: &[usize],
+        loop_mode: LoopMode,
+    ) -> AnimationId {
+        let frames = delays.iter().map(|&delay| (delay, vec![])).collect();
+        animations.add_test_animation(frames, loop_mode)
+    }
+
+    #[test]
+    fn hold_stays_on_the_last_frame() {
+        let mut animations = AnimationStore::new();
+        let held = test_animation(&mut animations, &[1, 1], LoopMode::Hold);
+        let mut animator = Animator::new(Frame::new(), held);
+
+        let frames: Vec<_> = play(&animations, &mut animator, 5)
+            .into_iter()
+            .map(|(_, frame, _)| frame)
+            .collect();
+        assert_eq!(frames, [0, 1, 1, 1, 1]);
+
+        let mut frame = Frame::new();
+        for _ in 0..5 {
+            frame.tick(false);
+        }
+        assert_eq!(animator.progress(&animations, frame), FixedPoint::ONE);
+    }
+
+    #[test]
+    fn loop_from_skips_the_frames_before_it() {
+        let mut animations = AnimationStore::new();
+        let looping = test_animation(&mut animations, &[1, 1, 1], LoopMode::LoopFrom(1));
+        let mut animator = Animator::new(Frame::new(), looping);
+
+        let frames: Vec<_> = play(&animations, &mut animator, 7)
+            .into_iter()
+            .map(|(_, frame, _)| frame)
+            .collect();
+        assert_eq!(frames, [0, 1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn queued_animations_play_in_order() {
+        let mut animations = AnimationStore::new();
+        let first = test_animation(&mut animations, &[1, 1], LoopMode::Loop);
+        let second = test_animation(&mut animations, &[1], LoopMode::Loop);
+        let third = test_animation(&mut animations, &[2], LoopMode::Hold);
+        let mut animator = Animator::new(Frame::new(), first);
+        animator.queue_animation(second);
+        animator.queue_animation(third);
+
+        let played: Vec<_> = play(&animations, &mut animator, 6)
+            .into_iter()
+            .map(|(animation, frame, _)| (animation, frame))
+            .collect();
+        assert_eq!(
+            played,
+            [
+                (first, 0),
+                (first, 1),
+                (second, 0),
+                (third, 0),
+                (third, 0),
+                (third, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn state_after_animation_waits_for_the_queue() {
+        let mut animations = AnimationStore::new();
+        let first = test_animation(&mut animations, &[1, 1], LoopMode::Loop);
+        let second = test_animation(&mut animations, &[2], LoopMode::Hold);
+        let mut animator = Animator::new(Frame::new(), first);
+        animator.switch_animation(Frame::new(), first, Some(IdleState.wrap()));
+        animator.queue_animation(second);
+
+        let requests: Vec<_> = play(&animations, &mut animator, 7)
+            .into_iter()
+            .map(|(_, _, request)| request)
+            .collect();
+        // The first animation is over on the third tick, the second one on the fifth
+        assert_eq!(requests, [false, false, false, false, true, false, false]);
+    }
+
+    #[test]
+    fn scripted_state_ends_at_its_progress() {
+        let info = GameInfo::create(None).unwrap();
+        let dude = CharacterChoice {
+            character: Character::Dude,
+            palette: 0,
+        };
+        let mut state = GameState::new(&info, dude, dude);
+        tick(&info, &mut state, &[]);
+        tick(&info, &mut state, &[Action::Punch]);
+        for _ in 0..20 {
+            tick(&info, &mut state, &[]);
+        }
+
+        let log = info.event_log.borrow();
+        let back_to_idle = log
+            .for_player(PlayerSide::Player1)
+            .find(|logged| {
+                matches!(
+                    logged.event,
+                    GameEvent::StateTransition {
+                        from: CharacterState::CharacterSpecific(_),
+                        to: CharacterState::Idle(_),
+                        ..
+                    }
+                )
+            })
+            .unwrap();
+        // The jab ends at 85% of its 13 frame animation, instead of once it is over
+        assert_eq!(back_to_idle.frame.number(), 1 + 12);
+    }
+
+    #[test]
+    fn rollback_rewinds_the_log() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[]);
+        let saved = state.clone();
+        tick(&info, &mut state, &[Action::Punch]);
+        assert!(info.event_log.borrow().iter().next().is_some());
+
+        info.event_log.borrow_mut().rewind(saved.current_frame);
+        assert!(info.event_log.borrow().iter().next().is_none());
+    }
+
+    #[test]
+    fn root_motion_moves_by_the_change_in_offset() {
+        let mut animations = AnimationStore::new();
+        let lunge = test_animation(&mut animations, &[1, 1, 1], LoopMode::Loop);
+        let offsets = [0usize, 2, 5].map(|x| Vec2::new(x.into(), FixedPoint::ZERO));
+        animations.set_test_root_motion(lunge, &offsets);
+        let mut animator = Animator::new(Frame::new(), lunge);
+
+        let mut frame = Frame::new();
+        let moved: Vec<_> = (0..6)
+            .map(|_| {
+                let tick =
+                    animator.tick(&animations, Facing::Left, &mut vec![], &mut vec![], frame);
+                frame.tick(false);
+                tick.root_motion.x
+            })
+            .collect();
+        // Looping carries on from the last offset instead of moving back
+        assert_eq!(
+            moved,
+            [0, 2, 3, 0, 2, 3].map(|x: usize| -FixedPoint::from(x))
+        );
+    }
+
+    #[test]
+    fn chip_damage_cannot_ko_unless_allowed() {
+        let (mut info, mut state) = start();
+        state.player_2.health = FixedPoint::from(3usize);
+        state.apply_effect(PlayerSide::Player2, &Effect::ChipDamage(5.into()), &info);
+        assert_eq!(state.player_2.health, FixedPoint::ONE);
+        state.apply_effect(PlayerSide::Player2, &Effect::ChipDamage(5.into()), &info);
+        assert_eq!(state.player_2.health, FixedPoint::ONE);
+
+        info.chip_can_kill = true;
+        state.apply_effect(PlayerSide::Player2, &Effect::ChipDamage(5.into()), &info);
+        assert_eq!(state.player_2.health, -FixedPoint::from(4usize));
+    }
+
+    #[test]
+    fn blocking_takes_guard_instead_of_health() {
+        let (info, mut state) = start();
+        state.player_2.position.x = state.player_1.position.x + FixedPoint::from(20.0);
+        let health = state.player_2.health;
+        for _ in 0..20 {
+            tick_both(&info, &mut state, &[Action::Punch], &[Action::MoveRight]);
+        }
+
+        assert_eq!(state.player_2.health, health);
+        assert_eq!(state.player_2.guard, FixedPoint::from(MAX_GUARD - 8));
+    }
+
+    #[test]
+    fn emptying_the_guard_crushes_it() {
+        let (info, mut state) = start();
+        state.player_2.guard = FixedPoint::from(5usize);
+        state.apply_effect(PlayerSide::Player2, &Effect::GuardDamage(8.into()), &info);
+        tick(&info, &mut state, &[]);
+
+        assert_eq!(state.player_2.guard, FixedPoint::ZERO);
+        assert!(matches!(
+            state.player_2.current_state,
+            CharacterState::GuardCrush(_)
+        ));
+        assert!(state.player_2.is_in_hitstun());
+        for _ in 0..GUARD_CRUSH_STUN {
+            tick(&info, &mut state, &[]);
+        }
+        assert!(matches!(
+            state.player_2.current_state,
+            CharacterState::Idle(_)
+        ));
+    }
+
+    #[test]
+    fn guard_regenerates_after_a_delay() {
+        let (info, mut state) = start();
+        state.apply_effect(PlayerSide::Player2, &Effect::GuardDamage(8.into()), &info);
+        let guard = FixedPoint::from(MAX_GUARD - 8);
+        for _ in 0..GUARD_REGEN_DELAY {
+            tick(&info, &mut state, &[]);
+        }
+        assert_eq!(state.player_2.guard, guard);
+
+        tick(&info, &mut state, &[]);
+        assert!(state.player_2.guard > guard);
+    }
+
+    #[test]
+    fn walking_and_knockback_use_the_character_stats() {
+        let info = GameInfo::create(None).unwrap();
+        let choice = |character| CharacterChoice {
+            character,
+            palette: 0,
+        };
+        let mut state = GameState::new(&info, choice(Character::Guy), choice(Character::Dude));
+        let guy = &info.character_protos[&Character::Guy].stats;
+        let dude = &info.character_protos[&Character::Dude].stats;
+
+        // Friction slows the players down once they have moved, so compare how far they went
+        let delta = FixedPoint::from(1.0 / 60.0);
+        let (p1_x, p2_x) = (state.player_1.position.x, state.player_2.position.x);
+        tick_both(&info, &mut state, &[Action::MoveRight], &[Action::MoveLeft]);
+        assert_eq!(state.player_1.position.x - p1_x, guy.walk_speed * delta);
+        assert_eq!(state.player_2.position.x - p2_x, -dude.walk_speed * delta);
+
+        let knockback = Effect::Knockback(FixedPoint::from(30usize));
+        state.apply_effect(PlayerSide::Player1, &knockback, &info);
+        state.apply_effect(PlayerSide::Player2, &knockback, &info);
+        assert_eq!(
+            state.player_1.velocity.x,
+            -FixedPoint::from(30usize) / guy.weight
+        );
+        assert_eq!(
+            state.player_2.velocity.x,
+            FixedPoint::from(30usize) / dude.weight
+        );
+    }
+
+    /// Jumps straight up with player 1 and returns the number of frames spent in jump squat
+    fn jump(info: &GameInfo, state: &mut GameState, actions: &[Action]) -> usize {
+        let mut frames = 0;
+        for _ in 0..20 {
+            tick(info, state, actions);
+            match state.player_1.current_state {
+                CharacterState::JumpSquat(_) => {
+                    assert!(state.player_1.grounded);
+                    frames += 1;
+                }
+                CharacterState::Airborne(_) => break,
+                _ => {}
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn jumps_leave_the_ground_after_the_jump_squat() {
+        let (info, mut state) = start();
+        let stats = &info.character_protos[&Character::Guy].stats;
+
+        assert_eq!(
+            jump(&info, &mut state, &[Action::MoveUp]),
+            stats.jump_squat_frames
+        );
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Airborne(AirborneState(JumpDirection::Up, JumpKind::Normal))
+        ));
+        assert_eq!(state.player_1.velocity.x, FixedPoint::ZERO);
+    }
+
+    #[test]
+    fn jump_direction_can_change_during_the_jump_squat() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[Action::MoveUp]);
+        jump(&info, &mut state, &[Action::MoveUp, Action::MoveRight]);
+
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Airborne(AirborneState(JumpDirection::Right, JumpKind::Normal))
+        ));
+        let stats = &info.character_protos[&Character::Guy].stats;
+        assert_eq!(state.player_1.velocity.x, stats.jump_speed);
+    }
+
+    #[test]
+    fn down_then_up_super_jumps() {
+        let (info, mut state) = start();
+        tick(&info, &mut state, &[Action::MoveDown]);
+        jump(&info, &mut state, &[Action::MoveUp]);
+
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Airborne(AirborneState(_, JumpKind::Super))
+        ));
+    }
+
+    #[test]
+    fn air_jumps_are_limited_by_the_stats() {
+        let (info, mut state) = start();
+        let stats = &info.character_protos[&Character::Guy].stats;
+        assert_eq!(stats.air_jumps, 1);
+        jump(&info, &mut state, &[Action::MoveUp]);
+
+        for _ in 0..3 {
+            tick(&info, &mut state, &[]);
+            tick(&info, &mut state, &[Action::MoveUp]);
+        }
+        let double_jumps = info
+            .event_log
+            .borrow()
+            .iter()
+            .filter(|logged| {
+                matches!(
+                    logged.event,
+                    GameEvent::StateTransition {
+                        to: CharacterState::Airborne(AirborneState(_, JumpKind::Double)),
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(double_jumps, stats.air_jumps);
+        assert_eq!(state.player_1.air_jumps_left, 0);
+    }
+
+    #[test]
+    fn air_dashes_once_per_jump() {
+        let (info, mut state) = start();
+        jump(&info, &mut state, &[Action::MoveUp]);
+        for actions in [&[Action::MoveRight][..], &[], &[Action::MoveRight]] {
+            tick(&info, &mut state, actions);
+        }
+        assert!(matches!(
+            &state.player_1.current_state,
+            CharacterState::AirDash(dash) if dash.direction == DashDirection::Forward
+        ));
+        // Dashing holds the player in the air
+        let y = state.player_1.position.y;
+        tick(&info, &mut state, &[]);
+        assert_eq!(state.player_1.position.y, y);
+
+        for _ in 0..info.character_protos[&Character::Guy].stats.air_dash_frames {
+            tick(&info, &mut state, &[]);
+        }
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Airborne(_)
+        ));
+        for actions in [&[Action::MoveLeft][..], &[], &[Action::MoveLeft]] {
+            tick(&info, &mut state, actions);
+        }
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Airborne(_)
+        ));
+    }
+
+    #[test]
+    fn holding_forward_after_a_dash_runs() {
+        let (info, mut state) = start();
+        let stats = &info.character_protos[&Character::Guy].stats;
+        for actions in [&[Action::MoveRight][..], &[], &[Action::MoveRight]] {
+            tick(&info, &mut state, actions);
+        }
+        assert!(matches!(
+            &state.player_1.current_state,
+            CharacterState::Dash(dash) if dash.direction == DashDirection::Forward
+        ));
+
+        for _ in 0..=stats.dash_frames {
+            tick(&info, &mut state, &[Action::MoveRight]);
+        }
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Run(_)
+        ));
+        let x = state.player_1.position.x;
+        tick(&info, &mut state, &[Action::MoveRight]);
+        let delta = FixedPoint::from(1.0 / 60.0);
+        assert_eq!(state.player_1.position.x - x, stats.run_speed * delta);
+
+        tick(&info, &mut state, &[]);
+        tick(&info, &mut state, &[]);
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Idle(_)
+        ));
+    }
+
+    #[test]
+    fn releasing_forward_ends_the_dash_without_running() {
+        let (info, mut state) = start();
+        for actions in [&[Action::MoveRight][..], &[], &[Action::MoveRight]] {
+            tick(&info, &mut state, actions);
+        }
+        for _ in 0..=info.character_protos[&Character::Guy].stats.dash_frames {
+            tick(&info, &mut state, &[]);
+        }
+        assert!(matches!(
+            state.player_1.current_state,
+            CharacterState::Idle(_)
+        ));
+    }
+
+    #[test]
+    fn root_motion_stops_at_the_walls_and_during_super_freeze() {
+        let (mut info, mut state) = start();
+        let step_back = test_animation(&mut info.animations, &[1, 1, 1], LoopMode::Loop);
+        let offsets = [0usize, 5, 10].map(|x| Vec2::new(-FixedPoint::from(x), FixedPoint::ZERO));
+        info.animations.set_test_root_motion(step_back, &offsets);
+        // Hitstun doesn't change the animation once it has started
+        state.player_1.current_state = HitstunState(100).wrap();
+        state.player_1.animator = Animator::new(state.current_frame, step_back);
+        let wall = -FixedPoint::from(STAGE_HALF_WIDTH);
+        state.player_1.position.x = wall + FixedPoint::from(7usize);
+
+        tick(&info, &mut state, &[]);
+        tick(&info, &mut state, &[]);
+        assert_eq!(state.player_1.position.x, wall + FixedPoint::from(2usize));
+
+        state.super_freeze = 5;
+        for _ in 0..5 {
+            tick(&info, &mut state, &[]);
+        }
+        assert_eq!(state.player_1.position.x, wall + FixedPoint::from(2usize));
+
+        tick(&info, &mut state, &[]);
+        assert_eq!(state.player_1.position.x, wall);
     }
 }
@@ -1,96 +1,329 @@
-use std::{collections::HashMap, path::Path};
+mod atlas;
+mod binary;
+mod export;
+mod lint;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Index,
+    path::{Path, PathBuf},
+};
 
+use image::RgbaImage;
 use sdl2::{
     pixels::PixelFormatEnum,
     render::{Texture, TextureCreator},
     video::WindowContext,
 };
+use serde::{Deserialize, Serialize};
+
+use super::{
+    fixed_point::{FixedPoint, Vec2},
+    palette::Palette,
+};
+
+pub use atlas::Sprite;
+pub use export::{export, ExportOptions};
+pub use lint::lint;
+
+use atlas::{Atlas, Cell};
+
+/// Handle to an animation in an `AnimationStore`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AnimationId(usize);
+
+/// Owns every loaded animation. The game state only refers to animations by id, which keeps it
+/// hashable and serializable and lets animations be replaced while the game is running.
+pub struct AnimationStore {
+    animations: Vec<Animation>,
+    /// File each animation was loaded from
+    paths: Vec<PathBuf>,
+    atlases: Vec<Atlas>,
+}
+
+impl AnimationStore {
+    pub fn new() -> Self {
+        Self {
+            animations: vec![],
+            paths: vec![],
+            atlases: vec![],
+        }
+    }
+
+    /// Loads animations that share an atlas, in the same order as `paths`. Without a texture
+    /// creator only the frame data is loaded, which is enough to run the simulation.
+    pub fn load_group(
+        &mut self,
+        paths: &[impl AsRef<Path>],
+        palettes: &[Palette],
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Vec<AnimationId>, AnimationError> {
+        let paths: Vec<PathBuf> = paths.iter().map(|path| path.as_ref().to_path_buf()).collect();
+        let (animations, atlas) =
+            load_group(&paths, self.atlases.len(), palettes, texture_creator)?;
+        self.atlases.push(atlas);
+
+        let first = self.animations.len();
+        self.animations.extend(animations);
+        self.paths.extend(paths);
+        Ok((first..self.animations.len()).map(AnimationId).collect())
+    }
+
+    /// Loads a group of animations again from their files and repacks their atlas. The old
+    /// animations are kept if `ids` isn't the whole group, loading fails or `check` rejects one of
+    /// the new animations.
+    pub fn reload_group(
+        &mut self,
+        ids: &[AnimationId],
+        palettes: &[Palette],
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+        check: impl Fn(&Animation) -> Result<(), AnimationErrorKind>,
+    ) -> Result<(), AnimationError> {
+        let atlas = self.animations[ids[0].0].atlas;
+        let group_size = self
+            .animations
+            .iter()
+            .filter(|animation| animation.atlas == atlas)
+            .count();
+        let paths: Vec<PathBuf> = ids.iter().map(|id| self.paths[id.0].clone()).collect();
+        if group_size != ids.len() || ids.iter().any(|id| self.animations[id.0].atlas != atlas) {
+            return Err(AnimationError {
+                path: paths[0].clone(),
+                kind: AnimationErrorKind::PartialGroup,
+            });
+        }
+
+        let (animations, new_atlas) = load_group(&paths, atlas, palettes, texture_creator)?;
+        for (path, animation) in paths.iter().zip(&animations) {
+            check(animation).map_err(|kind| AnimationError {
+                path: path.clone(),
+                kind,
+            })?;
+        }
+        self.atlases[atlas] = new_atlas;
+        for (id, animation) in ids.iter().zip(animations) {
+            self.animations[id.0] = animation;
+        }
+        Ok(())
+    }
 
-use super::fixed_point::Vec2;
+    pub fn path(&self, id: AnimationId) -> &Path {
+        &self.paths[id.0]
+    }
+
+    /// Adds an animation without any boxes or sprites, with a frame for each delay and events
+    #[cfg(test)]
+    pub fn add_test_animation(
+        &mut self,
+        frames: Vec<(usize, Vec<FrameEvent>)>,
+        loop_mode: LoopMode,
+    ) -> AnimationId {
+        let zero = Vec2::new(FixedPoint::ZERO, FixedPoint::ZERO);
+        self.animations.push(Animation {
+            atlas: 0,
+            cell_width: 1,
+            cell_height: 1,
+            frame_data: frames
+                .into_iter()
+                .map(|(delay, events)| FrameData {
+                    delay,
+                    origin: zero,
+                    sprite: Sprite::default(),
+                    root_motion: zero,
+                    hitboxes: HashMap::new(),
+                    events,
+                })
+                .collect(),
+            hitboxes: HashMap::new(),
+            loop_mode,
+            startup: 0,
+            active_frames: 0,
+        });
+        self.paths.push(PathBuf::new());
+        AnimationId(self.animations.len() - 1)
+    }
+
+    #[cfg(test)]
+    pub fn set_test_root_motion(&mut self, id: AnimationId, offsets: &[Vec2]) {
+        for (frame, &offset) in self.animations[id.0].frame_data.iter_mut().zip(offsets) {
+            frame.root_motion = offset;
+        }
+    }
+
+    /// The texture the sprite is on, in the given palette
+    pub fn texture(&self, animation: &Animation, sprite: &Sprite, palette: usize) -> &Texture {
+        self.atlases[animation.atlas].texture(sprite.page, palette)
+    }
+}
+
+impl Index<AnimationId> for AnimationStore {
+    type Output = Animation;
+
+    fn index(&self, id: AnimationId) -> &Animation {
+        &self.animations[id.0]
+    }
+}
 
 pub struct Animation {
-    pub texture_atlas: Texture<'static>,
+    /// Index of the atlas in the `AnimationStore` the frames are packed into
+    atlas: usize,
     pub cell_width: usize,
     pub cell_height: usize,
-    pub columns: usize,
     pub frame_data: Vec<FrameData>,
     pub hitboxes: HashMap<usize, HitboxInfo>,
+    pub loop_mode: LoopMode,
 
     pub startup: usize,
     /// also includes gaps in active frames
     pub active_frames: usize,
-    pub recovery: usize,
+}
+
+/// An animation file that couldn't be loaded
+#[derive(Debug)]
+pub struct AnimationError {
+    pub path: PathBuf,
+    pub kind: AnimationErrorKind,
+}
+
+#[derive(Debug)]
+pub enum AnimationErrorKind {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Binary(binary::BinaryError),
+    Image(image::ImageError),
+    Texture(String),
+    /// A number that doesn't fit in a `FixedPoint`
+    InvalidNumber {
+        frame: usize,
+        hitbox: Option<usize>,
+        field: &'static str,
+        value: f32,
+    },
+    /// A frame uses a hitbox that isn't declared in the spritesheet info
+    UnknownHitbox { frame: usize, hitbox: usize },
+    /// A frame's cell isn't entirely inside the spritesheet
+    CellOutOfBounds { frame: usize },
+    /// The animation loops from a frame it doesn't have
+    InvalidLoopFrame { frame: usize },
+    /// Only some of the animations of a group were reloaded, when they share an atlas
+    PartialGroup,
+    /// A frame spawns an effect the character has no animation for
+    UnknownEffect { frame: usize, animation: String },
+    /// A frame spawns a projectile the character doesn't have
+    UnknownProjectile { frame: usize, projectile: String },
+}
+
+impl Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        match &self.kind {
+            AnimationErrorKind::Io(e) => write!(f, "couldn't read file: {e}"),
+            AnimationErrorKind::Json(e) => write!(f, "invalid animation JSON: {e}"),
+            AnimationErrorKind::Binary(e) => write!(f, "{e}"),
+            AnimationErrorKind::Image(e) => write!(f, "invalid spritesheet: {e}"),
+            AnimationErrorKind::Texture(e) => write!(f, "couldn't create texture: {e}"),
+            AnimationErrorKind::InvalidNumber {
+                frame,
+                hitbox: Some(hitbox),
+                field,
+                value,
+            } => write!(f, "frame {frame}, hitbox {hitbox}: invalid {field} value {value}"),
+            AnimationErrorKind::InvalidNumber {
+                frame,
+                hitbox: None,
+                field,
+                value,
+            } => write!(f, "frame {frame}: invalid {field} value {value}"),
+            AnimationErrorKind::UnknownHitbox { frame, hitbox } => {
+                write!(f, "frame {frame}, hitbox {hitbox}: hitbox isn't declared")
+            }
+            AnimationErrorKind::CellOutOfBounds { frame } => {
+                write!(f, "frame {frame}: cell is outside the spritesheet")
+            }
+            AnimationErrorKind::InvalidLoopFrame { frame } => {
+                write!(f, "loops from frame {frame}, which doesn't exist")
+            }
+            AnimationErrorKind::PartialGroup => {
+                write!(f, "only whole groups of animations can be reloaded")
+            }
+            AnimationErrorKind::UnknownEffect { frame, animation } => {
+                write!(f, "frame {frame}: effect {animation} isn't an animation of the character")
+            }
+            AnimationErrorKind::UnknownProjectile { frame, projectile } => {
+                write!(f, "frame {frame}: projectile {projectile} isn't a projectile of the character")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            AnimationErrorKind::Io(e) => Some(e),
+            AnimationErrorKind::Json(e) => Some(e),
+            AnimationErrorKind::Binary(e) => Some(e),
+            AnimationErrorKind::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// What an animation does once its last frame is over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LoopMode {
+    /// Starts over from the first frame
+    #[default]
+    Loop,
+    /// Stays on the last frame
+    Hold,
+    /// Starts over from the given frame, to loop only the end of the animation
+    LoopFrom(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackPhase {
+    Startup,
+    Active,
+    Recovery,
 }
 
 impl Animation {
-    pub fn load(
-        path: impl AsRef<Path>,
-        texture_creator: &'static TextureCreator<WindowContext>,
-    ) -> Result<&'static Self, ()> {
-        let anim: interface::Animation =
-            serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    /// Which part of the attack the animation is in after the given amount of frames
+    pub fn phase(&self, elapsed: usize) -> AttackPhase {
+        if elapsed < self.startup {
+            AttackPhase::Startup
+        } else if elapsed < self.startup + self.active_frames {
+            AttackPhase::Active
+        } else {
+            AttackPhase::Recovery
+        }
+    }
 
-        let img_data = image::load_from_memory(&anim.spritesheet)
-            .unwrap()
-            .into_rgba8();
-        let mut texture = texture_creator
-            .create_texture(
-                PixelFormatEnum::ABGR8888,
-                sdl2::render::TextureAccess::Static,
-                img_data.width(),
-                img_data.height(),
-            )
-            .unwrap();
-        println!("{:?}", texture.blend_mode());
-        texture.set_blend_mode(sdl2::render::BlendMode::Blend);
-        texture
-            .update(None, &img_data, img_data.width() as usize * 4)
-            .unwrap();
+    /// Frames it takes to play every frame of the animation once
+    pub fn duration(&self) -> usize {
+        self.frame_data.iter().map(|fd| fd.delay).sum()
+    }
 
-        let frame_data: Vec<FrameData> = anim
-            .info
-            .frame_data
-            .into_iter()
-            .map(|fd| FrameData {
-                delay: fd.delay,
-                origin: Vec2::new(
-                    fd.origin[0].try_into().unwrap(),
-                    fd.origin[1].try_into().unwrap(),
-                ),
-                root_motion: Vec2::new(
-                    fd.root_motion[0].try_into().unwrap(),
-                    fd.root_motion[1].try_into().unwrap(),
-                ),
-                hitboxes: fd
-                    .hitboxes
-                    .into_iter()
-                    .filter_map(|(id, hp)| {
-                        if hp.enabled {
-                            Some((
-                                id,
-                                HitboxPosition {
-                                    id,
-                                    pos: Vec2::new(
-                                        hp.pos[0].try_into().unwrap(),
-                                        (-hp.pos[1]).try_into().unwrap(),
-                                    ),
-                                    size: Vec2::new(
-                                        hp.size[0].try_into().unwrap(),
-                                        hp.size[1].try_into().unwrap(),
-                                    ),
-                                    enabled: hp.enabled,
-                                },
-                            ))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect(),
-            })
-            .collect();
+    /// Builds the animation from its file, with the frames already packed into `sprites`
+    fn new(
+        path: &Path,
+        info: interface::SpritesheetInfo,
+        atlas: usize,
+        sprites: &[Sprite],
+    ) -> Result<Self, AnimationError> {
+        let error = |kind| AnimationError {
+            path: path.to_path_buf(),
+            kind,
+        };
+
+        if let LoopMode::LoopFrom(frame) = info.loop_mode {
+            if frame >= info.frame_data.len() {
+                return Err(error(AnimationErrorKind::InvalidLoopFrame { frame }));
+            }
+        }
 
-        let hitboxes: HashMap<usize, HitboxInfo> = anim
-            .info
+        let hitboxes: HashMap<usize, HitboxInfo> = info
             .hitboxes
             .into_iter()
             .map(|(id, hi)| {
@@ -105,77 +338,321 @@ impl Animation {
             })
             .collect();
 
-        let mut state = 0;
-
-        let mut startup = 0;
-        let mut active_frames = 0;
-        let mut recovery = 0;
-
-        for frame in &frame_data {
-            match state {
-                0 => for (id, _) in &frame.hitboxes {
-                    if !hitboxes[id].is_hurtbox {
-                        state = 1;
-                        break;
-                    }
-                },
-                1 => {
-                    let mut no_hitbox = true;
-                    for (id, _) in &frame.hitboxes {
-                        if !hitboxes[id].is_hurtbox {
-                            no_hitbox = false;
-                            break;
-                        }
-                    }
-
-                    if no_hitbox {
-                        state = 2;
-                    }
-                },
-                2 => {
-                    for (id, _) in &frame.hitboxes {
-                        if !hitboxes[id].is_hurtbox {
-                            active_frames += recovery;
-                            recovery = 0;
-                            state = 1;
-                            break;
-                        }
-                    }
+        let mut frame_data = Vec::with_capacity(info.frame_data.len());
+        for ((frame, fd), &sprite) in info.frame_data.into_iter().enumerate().zip(sprites) {
+            let vec2 = |field, [x, y]: [f32; 2], hitbox| {
+                let fixed = |value| {
+                    to_fixed(value).ok_or(error(AnimationErrorKind::InvalidNumber {
+                        frame,
+                        hitbox,
+                        field,
+                        value,
+                    }))
+                };
+                Ok::<_, AnimationError>(Vec2::new(fixed(x)?, fixed(y)?))
+            };
+
+            let mut frame_hitboxes = HashMap::new();
+            for (id, hp) in fd.hitboxes {
+                if !hitboxes.contains_key(&id) {
+                    return Err(error(AnimationErrorKind::UnknownHitbox { frame, hitbox: id }));
+                }
+                if !hp.enabled {
+                    continue;
                 }
-                _ => unreachable!()
+                frame_hitboxes.insert(
+                    id,
+                    HitboxPosition {
+                        id,
+                        pos: vec2("pos", [hp.pos[0], -hp.pos[1]], Some(id))?,
+                        size: vec2("size", hp.size, Some(id))?,
+                        enabled: hp.enabled,
+                    },
+                );
             }
 
-            match state {
-                0 => startup += frame.delay,
-                1 => active_frames += frame.delay,
-                2 => recovery += frame.delay,
-                _ => unreachable!()
-            }
+            // The origin is moved along with the top left corner of the trimmed sprite
+            let offset = Vec2::new(
+                FixedPoint::from(sprite.offset_x as usize),
+                FixedPoint::from(sprite.offset_y as usize),
+            );
+            let events = fd
+                .events
+                .into_iter()
+                .map(|event| {
+                    Ok(match event {
+                        interface::FrameEvent::PlaySound(sound) => FrameEvent::PlaySound(sound),
+                        interface::FrameEvent::SpawnEffect { animation, offset } => {
+                            FrameEvent::SpawnEffect {
+                                animation,
+                                offset: vec2("effect offset", offset, None)?,
+                            }
+                        }
+                        interface::FrameEvent::SpawnProjectile(name) => {
+                            FrameEvent::SpawnProjectile(name)
+                        }
+                        interface::FrameEvent::SetInvincible(invincible) => {
+                            FrameEvent::SetInvincible(invincible)
+                        }
+                        interface::FrameEvent::CancelWindow(open) => FrameEvent::CancelWindow(open),
+                        interface::FrameEvent::CounterState(state) => {
+                            FrameEvent::CounterState(state)
+                        }
+                    })
+                })
+                .collect::<Result<_, AnimationError>>()?;
+
+            frame_data.push(FrameData {
+                delay: fd.delay,
+                origin: vec2("origin", fd.origin, None)? - offset,
+                sprite,
+                root_motion: vec2("root_motion", fd.root_motion, None)?,
+                hitboxes: frame_hitboxes,
+                events,
+            });
         }
 
-        println!("startup: {startup}");
-        println!("active frames: {active_frames}");
-        println!("recovery: {recovery}");
+        let (startup, active_frames, _) = attack_phases(frame_data.iter().map(|frame| {
+            let has_hitbox = frame.hitboxes.keys().any(|id| !hitboxes[id].is_hurtbox);
+            (frame.delay, has_hitbox)
+        }));
 
-        Ok(Box::leak(Box::new(Animation {
-            texture_atlas: texture,
-            cell_width: anim.info.cell_width,
-            cell_height: anim.info.cell_height,
-            columns: anim.info.columns,
+        Ok(Animation {
+            atlas,
+            cell_width: info.cell_width,
+            cell_height: info.cell_height,
             frame_data,
             hitboxes,
+            loop_mode: info.loop_mode,
             startup,
             active_frames,
-            recovery,
-        })))
+        })
+    }
+}
+
+/// Reads the animations and packs the frames of all of them into one atlas
+fn load_group(
+    paths: &[PathBuf],
+    atlas: usize,
+    palettes: &[Palette],
+    texture_creator: Option<&TextureCreator<WindowContext>>,
+) -> Result<(Vec<Animation>, Atlas), AnimationError> {
+    let files = paths
+        .iter()
+        .map(|path| read(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut cells = vec![];
+    for (path, (info, img_data)) in paths.iter().zip(&files) {
+        for frame in 0..info.frame_data.len() {
+            let (x, y) = cell_position(info, img_data, frame).ok_or_else(|| AnimationError {
+                path: path.clone(),
+                kind: AnimationErrorKind::CellOutOfBounds { frame },
+            })?;
+            cells.push(Cell {
+                image: img_data,
+                x,
+                y,
+                width: info.cell_width as u32,
+                height: info.cell_height as u32,
+            });
+        }
+    }
+
+    let (pages, sprites) = atlas::pack(&cells);
+    // There are only pages to create textures for if there is at least one animation
+    let textures = Atlas::new(&pages, palettes, texture_creator).map_err(|e| AnimationError {
+        path: paths[0].clone(),
+        kind: AnimationErrorKind::Texture(e),
+    })?;
+
+    let mut sprites = sprites.as_slice();
+    let animations = paths
+        .iter()
+        .zip(files)
+        .map(|(path, (info, _))| {
+            let (own, rest) = sprites.split_at(info.frame_data.len());
+            sprites = rest;
+            Animation::new(path, info, atlas, own)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((animations, textures))
+}
+
+/// Top left corner of a frame's cell in the spritesheet, if the cell is entirely inside it
+fn cell_position(
+    info: &interface::SpritesheetInfo,
+    img_data: &RgbaImage,
+    frame: usize,
+) -> Option<(u32, u32)> {
+    let x = frame.checked_rem(info.columns)? * info.cell_width;
+    let y = frame.checked_div(info.columns)? * info.cell_height;
+    if x + info.cell_width > img_data.width() as usize
+        || y + info.cell_height > img_data.height() as usize
+    {
+        return None;
+    }
+    Some((x as u32, y as u32))
+}
+
+/// Reads the spritesheet info and the spritesheet itself from either form of animation file
+fn read(path: &Path) -> Result<(interface::SpritesheetInfo, RgbaImage), AnimationError> {
+    let error = |kind| AnimationError {
+        path: path.to_path_buf(),
+        kind,
+    };
+
+    let data = std::fs::read(path).map_err(|e| error(AnimationErrorKind::Io(e)))?;
+    if binary::is_binary(&data) {
+        let anim = binary::read(&data).map_err(|e| error(AnimationErrorKind::Binary(e)))?;
+        let img_data = anim
+            .spritesheet
+            .decode()
+            .map_err(|e| error(AnimationErrorKind::Image(e)))?;
+        Ok((anim.info, img_data))
+    } else {
+        let anim: interface::Animation =
+            serde_json::from_slice(&data).map_err(|e| error(AnimationErrorKind::Json(e)))?;
+        let img_data = image::load_from_memory(&anim.spritesheet)
+            .map_err(|e| error(AnimationErrorKind::Image(e)))?
+            .into_rgba8();
+        Ok((anim.info, img_data))
+    }
+}
+
+/// Splits the frames into startup, active frames and recovery, given the delay of each frame
+/// and whether it has any hitbox that isn't a hurtbox. Gaps between active frames count as
+/// active.
+fn attack_phases(frames: impl IntoIterator<Item = (usize, bool)>) -> (usize, usize, usize) {
+    let mut phase = AttackPhase::Startup;
+
+    let mut startup = 0;
+    let mut active_frames = 0;
+    let mut recovery = 0;
+
+    for (delay, has_hitbox) in frames {
+        match phase {
+            AttackPhase::Startup if has_hitbox => phase = AttackPhase::Active,
+            AttackPhase::Active if !has_hitbox => phase = AttackPhase::Recovery,
+            AttackPhase::Recovery if has_hitbox => {
+                active_frames += recovery;
+                recovery = 0;
+                phase = AttackPhase::Active;
+            }
+            _ => {}
+        }
+
+        match phase {
+            AttackPhase::Startup => startup += delay,
+            AttackPhase::Active => active_frames += delay,
+            AttackPhase::Recovery => recovery += delay,
+        }
+    }
+
+    (startup, active_frames, recovery)
+}
+
+/// Converts an animation between the JSON and binary forms. The direction is picked from the
+/// form of the input. `raw` stores the spritesheet as uncompressed pixels in the binary form.
+pub fn convert(input: &Path, output: &Path, raw: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(input)?;
+
+    let converted = if binary::is_binary(&data) {
+        let anim = binary::read(&data)?;
+        let anim = interface::Animation {
+            spritesheet: anim.spritesheet.encode_png()?,
+            info: anim.info,
+        };
+        serde_json::to_vec_pretty(&anim)?
+    } else {
+        let anim: interface::Animation = serde_json::from_slice(&data)?;
+        let spritesheet = if raw {
+            binary::Spritesheet::Raw(image::load_from_memory(&anim.spritesheet)?.into_rgba8())
+        } else {
+            binary::Spritesheet::Png(anim.spritesheet)
+        };
+        binary::write(&binary::BinaryAnimation {
+            info: anim.info,
+            spritesheet,
+        })
+        .map_err(|e| AnimationError {
+            path: output.to_path_buf(),
+            kind: AnimationErrorKind::Binary(e),
+        })?
+    };
+
+    std::fs::write(output, converted)?;
+    Ok(())
+}
+
+/// `FixedPoint::from` silently wraps around for values that are out of range
+fn to_fixed(value: f32) -> Option<FixedPoint> {
+    (value.is_finite() && value.abs() < (1 << 23) as f32).then(|| value.into())
+}
+
+fn create_texture(
+    img_data: &RgbaImage,
+    texture_creator: &TextureCreator<WindowContext>,
+) -> Result<Texture, String> {
+    let mut texture = texture_creator
+        .create_texture(
+            PixelFormatEnum::ABGR8888,
+            sdl2::render::TextureAccess::Static,
+            img_data.width(),
+            img_data.height(),
+        )
+        .map_err(|e| e.to_string())?;
+    texture.set_blend_mode(sdl2::render::BlendMode::Blend);
+    if let Err(e) = texture.update(None, img_data, img_data.width() as usize * 4) {
+        // SAFETY: the texture was just created by `texture_creator`, whose renderer is still alive
+        unsafe { texture.destroy() };
+        return Err(e.to_string());
     }
+    Ok(texture)
 }
 
 pub struct FrameData {
     pub delay: usize,
+    /// Relative to the top left corner of the trimmed sprite
     pub origin: Vec2,
+    pub sprite: Sprite,
+    /// Offset from where the character was when the animation started
     pub root_motion: Vec2,
     pub hitboxes: HashMap<usize, HitboxPosition>,
+    /// Dispatched by the animator when the frame is entered
+    pub events: Vec<FrameEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameEvent {
+    /// Sounds aren't played yet, since there is no audio. They only show up in the event log.
+    PlaySound(String),
+    /// Plays an animation of the character once, without any gameplay effect
+    SpawnEffect {
+        animation: String,
+        /// Relative to the player, as if facing right
+        offset: Vec2,
+    },
+    /// Spawns a projectile from the character's projectile list
+    SpawnProjectile(String),
+    /// Makes the player invincible until it is turned off or the animation changes
+    SetInvincible(bool),
+    /// While open, the current state can be canceled into any move going to a state with a
+    /// higher priority
+    CancelWindow(bool),
+    /// Overrides what kind of counter hit the player would take, until the animation changes
+    CounterState(CounterState),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum CounterState {
+    /// Counter hit during startup and active frames, punish counter during recovery
+    #[default]
+    Auto,
+    NoCounter,
+    CounterHit,
+    PunishCounter,
 }
 
 pub struct HitboxInfo {
@@ -227,7 +704,7 @@ mod interface {
                 {
                     base64::engine::general_purpose::STANDARD_NO_PAD
                         .decode(v)
-                        .map_err(|_| panic!())
+                        .map_err(E::custom)
                 }
 
                 fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
@@ -268,6 +745,8 @@ mod interface {
         pub frame_count: usize,
         pub frame_data: Vec<FrameData>,
         pub hitboxes: HashMap<usize, HitboxInfo>,
+        #[serde(default)]
+        pub loop_mode: super::LoopMode,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -276,6 +755,22 @@ mod interface {
         pub origin: [f32; 2],
         pub root_motion: [f32; 2],
         pub hitboxes: HashMap<usize, HitboxPosition>,
+        #[serde(default)]
+        pub events: Vec<FrameEvent>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum FrameEvent {
+        PlaySound(String),
+        SpawnEffect {
+            animation: String,
+            #[serde(default)]
+            offset: [f32; 2],
+        },
+        SpawnProjectile(String),
+        SetInvincible(bool),
+        CancelWindow(bool),
+        CounterState(super::CounterState),
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -293,3 +788,151 @@ mod interface {
         pub is_hurtbox: bool,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_json(path: &Path) -> interface::Animation {
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn converted_back(raw: bool) -> (interface::Animation, interface::Animation) {
+        let input = Path::new("assets/animations/c1_fireball_throw.anim");
+        let dir = std::env::temp_dir();
+        let name = if raw { "raw" } else { "png" };
+        let binary = dir.join(format!("fg-sdl2-convert-{name}.fganim"));
+        let json = dir.join(format!("fg-sdl2-convert-{name}.anim"));
+
+        convert(input, &binary, raw).unwrap();
+        assert!(binary::is_binary(&std::fs::read(&binary).unwrap()));
+        convert(&binary, &json, raw).unwrap();
+
+        let result = (read_json(input), read_json(&json));
+        std::fs::remove_file(binary).unwrap();
+        std::fs::remove_file(json).unwrap();
+        result
+    }
+
+    fn info_json(anim: &interface::Animation) -> serde_json::Value {
+        serde_json::to_value(&anim.info).unwrap()
+    }
+
+    #[test]
+    fn convert_round_trips() {
+        let (original, converted) = converted_back(false);
+        assert_eq!(info_json(&converted), info_json(&original));
+        assert_eq!(converted.spritesheet, original.spritesheet);
+    }
+
+    #[test]
+    fn convert_round_trips_raw_spritesheets() {
+        let (original, converted) = converted_back(true);
+        assert_eq!(info_json(&converted), info_json(&original));
+        let pixels = |anim: &interface::Animation| {
+            image::load_from_memory(&anim.spritesheet)
+                .unwrap()
+                .into_rgba8()
+        };
+        assert_eq!(pixels(&converted), pixels(&original));
+    }
+
+    /// Copy of a shipped animation that the test can change, with the first frame's delay set
+    fn writable_copy(name: &str, delay: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("fg-sdl2-{name}.anim"));
+        set_first_delay(&path, Path::new("assets/animations/c1_punch.anim"), delay);
+        path
+    }
+
+    fn set_first_delay(path: &Path, from: &Path, delay: usize) {
+        let mut anim: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(from).unwrap()).unwrap();
+        anim["info"]["frame_data"][0]["delay"] = delay.into();
+        std::fs::write(path, serde_json::to_vec(&anim).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn groups_get_ids_in_the_order_of_their_paths() {
+        let paths = [
+            "assets/animations/c1_idle.anim",
+            "assets/animations/c1_punch.anim",
+        ];
+        let mut store = AnimationStore::new();
+        let first = store.load_group(&paths[..1], &[], None).unwrap();
+        let second = store.load_group(&paths, &[], None).unwrap();
+
+        assert_eq!(first.len(), 1);
+        assert!(!second.contains(&first[0]));
+        assert_eq!(store.path(second[1]), Path::new(paths[1]));
+        assert_eq!(
+            store[second[0]].frame_data.len(),
+            store[first[0]].frame_data.len()
+        );
+        assert_eq!(store[second[1]].frame_data.len(), 9);
+    }
+
+    #[test]
+    fn reloading_keeps_the_ids() {
+        let path = writable_copy("reload", 1);
+        let mut store = AnimationStore::new();
+        let other = store
+            .load_group(&["assets/animations/c1_idle.anim"], &[], None)
+            .unwrap();
+        let ids = store.load_group(&[&path], &[], None).unwrap();
+
+        set_first_delay(&path, &path, 7);
+        store.reload_group(&ids, &[], None, |_| Ok(())).unwrap();
+        assert_eq!(store[ids[0]].frame_data[0].delay, 7);
+        assert_eq!(
+            store.path(other[0]),
+            Path::new("assets/animations/c1_idle.anim")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failed_reloads_keep_the_old_animations() {
+        let path = writable_copy("failed-reload", 3);
+        let mut store = AnimationStore::new();
+        let ids = store.load_group(&[&path], &[], None).unwrap();
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(store.reload_group(&ids, &[], None, |_| Ok(())).is_err());
+        assert_eq!(store[ids[0]].frame_data[0].delay, 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reloading_part_of_a_group_fails() {
+        let paths = [
+            "assets/animations/c1_idle.anim",
+            "assets/animations/c1_punch.anim",
+        ];
+        let mut store = AnimationStore::new();
+        let ids = store.load_group(&paths, &[], None).unwrap();
+        let error = store
+            .reload_group(&ids[..1], &[], None, |_| Ok(()))
+            .err()
+            .unwrap();
+        assert!(matches!(error.kind, AnimationErrorKind::PartialGroup));
+        assert_eq!(store.path(ids[0]), Path::new(paths[0]));
+    }
+
+    #[test]
+    fn origins_follow_the_trimmed_sprites() {
+        let path = Path::new("assets/animations/c1_punch.anim");
+        let mut store = AnimationStore::new();
+        let id = store.load_group(&[path], &[], None).unwrap()[0];
+
+        let frames = &store[id].frame_data;
+        assert!(frames.iter().any(|frame| frame.sprite.offset_x > 0));
+        for (frame, original) in frames.iter().zip(read_json(path).info.frame_data) {
+            let offset = Vec2::new(
+                FixedPoint::from(frame.sprite.offset_x as usize),
+                FixedPoint::from(frame.sprite.offset_y as usize),
+            );
+            let origin = Vec2::new(original.origin[0].into(), original.origin[1].into());
+            assert_eq!(frame.origin + offset, origin);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use image::{GenericImageView, RgbaImage};
+use sdl2::{
+    render::{Texture, TextureCreator},
+    video::WindowContext,
+};
+
+use super::{super::palette::Palette, create_texture};
+
+/// Pages are started on a new one once they would get taller than this. Pages are at least this
+/// wide, and wider only if a single sprite is.
+const PAGE_SIZE: u32 = 2048;
+/// Transparent pixels between sprites, so scaling doesn't bleed one into another
+const PADDING: u32 = 1;
+
+/// A frame of an animation as it ended up in an atlas, with the transparent border trimmed off
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sprite {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Position of the trimmed sprite in the frame's original cell
+    pub offset_x: u32,
+    pub offset_y: u32,
+}
+
+impl Sprite {
+    /// Fully transparent frames are trimmed down to nothing, and aren't drawn
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+/// A frame's cell in a spritesheet
+pub struct Cell<'a> {
+    pub image: &'a RgbaImage,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Cell<'_> {
+    /// The cell cut down to the smallest rectangle containing every visible pixel
+    fn trim(&self) -> Sprite {
+        let mut min = (u32::MAX, u32::MAX);
+        let mut max = (0, 0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.image.get_pixel(self.x + x, self.y + y)[3] > 0 {
+                    min = (min.0.min(x), min.1.min(y));
+                    max = (max.0.max(x + 1), max.1.max(y + 1));
+                }
+            }
+        }
+
+        if min.0 == u32::MAX {
+            return Sprite::default();
+        }
+        Sprite {
+            width: max.0 - min.0,
+            height: max.1 - min.1,
+            offset_x: min.0,
+            offset_y: min.1,
+            ..Sprite::default()
+        }
+    }
+}
+
+/// Trims the cells and packs them onto as few pages as possible, placing them on shelves from
+/// the tallest to the shortest. Returns the pages, and the sprites in the same order as `cells`.
+pub fn pack(cells: &[Cell]) -> (Vec<RgbaImage>, Vec<Sprite>) {
+    let mut sprites: Vec<Sprite> = cells.iter().map(Cell::trim).collect();
+
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sprites[i].height));
+
+    let page_width = sprites
+        .iter()
+        .map(|sprite| sprite.width)
+        .max()
+        .unwrap_or(0)
+        .max(PAGE_SIZE);
+
+    let mut page_sizes: Vec<(u32, u32)> = vec![];
+    let mut shelf_x = 0;
+    let mut shelf_y = 0;
+    let mut shelf_height = 0;
+    for i in order {
+        let sprite = &mut sprites[i];
+        if sprite.is_empty() {
+            continue;
+        }
+
+        if shelf_x + sprite.width > page_width {
+            shelf_x = 0;
+            shelf_y += shelf_height + PADDING;
+            shelf_height = 0;
+        }
+        if page_sizes.is_empty() || (shelf_y > 0 && shelf_y + sprite.height > PAGE_SIZE) {
+            page_sizes.push((0, 0));
+            shelf_x = 0;
+            shelf_y = 0;
+            shelf_height = 0;
+        }
+
+        sprite.page = page_sizes.len() - 1;
+        sprite.x = shelf_x;
+        sprite.y = shelf_y;
+        shelf_x += sprite.width + PADDING;
+        shelf_height = shelf_height.max(sprite.height);
+
+        let size = page_sizes.last_mut().unwrap();
+        *size = (
+            size.0.max(sprite.x + sprite.width),
+            size.1.max(sprite.y + sprite.height),
+        );
+    }
+
+    let mut pages: Vec<RgbaImage> = page_sizes
+        .into_iter()
+        .map(|(width, height)| RgbaImage::new(width, height))
+        .collect();
+    for (cell, sprite) in cells.iter().zip(&sprites) {
+        if sprite.is_empty() {
+            continue;
+        }
+        let source = cell.image.view(
+            cell.x + sprite.offset_x,
+            cell.y + sprite.offset_y,
+            sprite.width,
+            sprite.height,
+        );
+        image::imageops::replace(
+            &mut pages[sprite.page],
+            &*source,
+            sprite.x as i64,
+            sprite.y as i64,
+        );
+    }
+
+    (pages, sprites)
+}
+
+/// Texture pages shared by animations that are loaded together, like those of a character
+pub struct Atlas {
+    /// One texture per palette for every page
+    pages: Vec<Vec<Texture>>,
+}
+
+impl Atlas {
+    /// Has no textures if there is no texture creator
+    pub fn new(
+        pages: &[RgbaImage],
+        palettes: &[Palette],
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Self, String> {
+        let mut atlas = Self { pages: vec![] };
+        let Some(texture_creator) = texture_creator else {
+            return Ok(atlas);
+        };
+        // Textures go straight into the atlas, so the ones created before a failure are destroyed
+        // when it is dropped
+        for page in pages {
+            atlas.pages.push(vec![]);
+            for palette in palettes {
+                let mut page = page.clone();
+                palette.apply(&mut page);
+                let texture = create_texture(&page, texture_creator)?;
+                atlas.pages.last_mut().unwrap().push(texture);
+            }
+        }
+        Ok(atlas)
+    }
+
+    pub fn texture(&self, page: usize, palette: usize) -> &Texture {
+        &self.pages[page][palette]
+    }
+}
+
+impl Drop for Atlas {
+    fn drop(&mut self) {
+        for texture in self.pages.drain(..).flatten() {
+            // SAFETY: atlases are owned by the `GameInfo`, which is dropped before the canvas
+            // whose renderer created the textures
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// Image with an opaque rectangle, colored by position so misplaced copies are caught
+    fn image_with_rect(width: u32, height: u32, rect: (u32, u32, u32, u32)) -> RgbaImage {
+        let (x, y, w, h) = rect;
+        RgbaImage::from_fn(width, height, |px, py| {
+            if (x..x + w).contains(&px) && (y..y + h).contains(&py) {
+                Rgba([px as u8, py as u8, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    fn cell(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Cell<'_> {
+        Cell {
+            image,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn trims_to_the_visible_pixels_of_the_cell() {
+        // Two 10x10 cells side by side, with something visible in the second one only
+        let image = image_with_rect(20, 10, (13, 2, 2, 4));
+
+        let empty = cell(&image, 0, 0, 10, 10).trim();
+        assert!(empty.is_empty());
+
+        let sprite = cell(&image, 10, 0, 10, 10).trim();
+        assert_eq!(
+            (
+                sprite.offset_x,
+                sprite.offset_y,
+                sprite.width,
+                sprite.height
+            ),
+            (3, 2, 2, 4)
+        );
+    }
+
+    #[test]
+    fn packs_trimmed_pixels_without_overlap() {
+        let image = image_with_rect(30, 10, (0, 0, 30, 10));
+        let cells: Vec<_> = (0..3).map(|i| cell(&image, i * 10, 0, 10, 10)).collect();
+        let (pages, sprites) = pack(&cells);
+
+        assert_eq!(pages.len(), 1);
+        for (i, (cell, sprite)) in cells.iter().zip(&sprites).enumerate() {
+            for y in 0..sprite.height {
+                for x in 0..sprite.width {
+                    assert_eq!(
+                        pages[0].get_pixel(sprite.x + x, sprite.y + y),
+                        image.get_pixel(cell.x + sprite.offset_x + x, cell.y + sprite.offset_y + y),
+                    );
+                }
+            }
+            for other in &sprites[i + 1..] {
+                let apart = sprite.x + sprite.width + PADDING <= other.x
+                    || other.x + other.width + PADDING <= sprite.x
+                    || sprite.y + sprite.height + PADDING <= other.y
+                    || other.y + other.height + PADDING <= sprite.y;
+                assert!(apart, "{sprite:?} overlaps {other:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_cells_take_no_space() {
+        let image = RgbaImage::new(10, 10);
+        let (pages, sprites) = pack(&[cell(&image, 0, 0, 10, 10)]);
+        assert!(pages.is_empty());
+        assert!(sprites[0].is_empty());
+    }
+
+    #[test]
+    fn starts_a_new_page_when_one_is_full() {
+        let size = PAGE_SIZE / 2 + 100;
+        let image = image_with_rect(size, size, (0, 0, size, size));
+        let cells = [
+            cell(&image, 0, 0, size, size),
+            cell(&image, 0, 0, size, size),
+        ];
+        let (pages, sprites) = pack(&cells);
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!((sprites[0].page, sprites[1].page), (0, 1));
+        assert_eq!((sprites[1].x, sprites[1].y), (0, 0));
+        assert!(pages.iter().all(|page| page.height() <= PAGE_SIZE));
+    }
+
+    #[test]
+    fn pages_are_widened_for_sprites_wider_than_a_page() {
+        let image = image_with_rect(PAGE_SIZE + 10, 2, (0, 0, PAGE_SIZE + 10, 2));
+        let (pages, sprites) = pack(&[cell(&image, 0, 0, PAGE_SIZE + 10, 2)]);
+
+        assert_eq!(pages[0].width(), PAGE_SIZE + 10);
+        assert_eq!(sprites[0].width, PAGE_SIZE + 10);
+    }
+}
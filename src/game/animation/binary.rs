@@ -0,0 +1,385 @@
+//! Binary container for animations, which is a lot faster to load than the JSON form.
+//!
+//! Layout, with all integers little endian:
+//! - `MAGIC`
+//! - format version as a `u16`. Version 1 has no frame events and version 2 no loop mode, and
+//!   both can still be read.
+//! - length of the spritesheet info as a `u32`, followed by the info encoded with bincode
+//! - image kind as a `u8`, either `IMAGE_PNG` or `IMAGE_RAW`
+//! - for raw images, the width and height as `u32`s
+//! - length of the image data as a `u32`, followed by the data
+
+use std::fmt::Display;
+
+use image::RgbaImage;
+
+use super::interface::SpritesheetInfo;
+
+pub const MAGIC: &[u8; 4] = b"FGAN";
+pub const VERSION: u16 = 3;
+
+const IMAGE_PNG: u8 = 0;
+const IMAGE_RAW: u8 = 1;
+
+pub enum Spritesheet {
+    /// PNG encoded, like in the JSON form
+    Png(Vec<u8>),
+    /// Already decoded RGBA pixels
+    Raw(RgbaImage),
+}
+
+impl Spritesheet {
+    pub fn decode(self) -> image::ImageResult<RgbaImage> {
+        match self {
+            Spritesheet::Png(data) => Ok(image::load_from_memory(&data)?.into_rgba8()),
+            Spritesheet::Raw(img) => Ok(img),
+        }
+    }
+
+    pub fn encode_png(self) -> image::ImageResult<Vec<u8>> {
+        match self {
+            Spritesheet::Png(data) => Ok(data),
+            Spritesheet::Raw(img) => {
+                let mut data = std::io::Cursor::new(vec![]);
+                img.write_to(&mut data, image::ImageOutputFormat::Png)?;
+                Ok(data.into_inner())
+            }
+        }
+    }
+}
+
+pub struct BinaryAnimation {
+    pub info: SpritesheetInfo,
+    pub spritesheet: Spritesheet,
+}
+
+#[derive(Debug)]
+pub enum BinaryError {
+    UnsupportedVersion(u16),
+    Truncated,
+    Info(bincode::Error),
+    UnknownImageKind(u8),
+    /// The raw image data doesn't match the size in the header
+    BadImageSize,
+}
+
+impl Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported binary animation version {version}, versions 1 to {VERSION} are supported"
+            ),
+            BinaryError::Truncated => write!(f, "binary animation ends unexpectedly"),
+            BinaryError::Info(e) => write!(f, "invalid spritesheet info: {e}"),
+            BinaryError::UnknownImageKind(kind) => write!(f, "unknown image kind {kind}"),
+            BinaryError::BadImageSize => write!(f, "raw image data doesn't match its size"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+/// Whether the data starts like a binary animation. Anything else is assumed to be JSON.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn read(data: &[u8]) -> Result<BinaryAnimation, BinaryError> {
+    let mut reader = Reader(&data[MAGIC.len()..]);
+
+    let version = u16::from_le_bytes(reader.take_array()?);
+    let info_len = reader.take_u32()? as usize;
+    let info_data = reader.take(info_len)?;
+    let info = match version {
+        1 => bincode::deserialize::<v1::SpritesheetInfo>(info_data)
+            .map_err(BinaryError::Info)?
+            .into(),
+        2 => bincode::deserialize::<v2::SpritesheetInfo>(info_data)
+            .map_err(BinaryError::Info)?
+            .into(),
+        VERSION => bincode::deserialize(info_data).map_err(BinaryError::Info)?,
+        _ => return Err(BinaryError::UnsupportedVersion(version)),
+    };
+
+    let spritesheet = match reader.take_array::<1>()?[0] {
+        IMAGE_PNG => {
+            let len = reader.take_u32()? as usize;
+            Spritesheet::Png(reader.take(len)?.to_vec())
+        }
+        IMAGE_RAW => {
+            let width = reader.take_u32()?;
+            let height = reader.take_u32()?;
+            let len = reader.take_u32()? as usize;
+            let img = RgbaImage::from_raw(width, height, reader.take(len)?.to_vec())
+                .ok_or(BinaryError::BadImageSize)?;
+            Spritesheet::Raw(img)
+        }
+        kind => return Err(BinaryError::UnknownImageKind(kind)),
+    };
+
+    Ok(BinaryAnimation { info, spritesheet })
+}
+
+pub fn write(animation: &BinaryAnimation) -> Result<Vec<u8>, BinaryError> {
+    let mut data = MAGIC.to_vec();
+    data.extend(VERSION.to_le_bytes());
+
+    let info = bincode::serialize(&animation.info).map_err(BinaryError::Info)?;
+    data.extend((info.len() as u32).to_le_bytes());
+    data.extend(info);
+
+    match &animation.spritesheet {
+        Spritesheet::Png(png) => {
+            data.push(IMAGE_PNG);
+            data.extend((png.len() as u32).to_le_bytes());
+            data.extend(png);
+        }
+        Spritesheet::Raw(img) => {
+            data.push(IMAGE_RAW);
+            data.extend(img.width().to_le_bytes());
+            data.extend(img.height().to_le_bytes());
+            data.extend((img.as_raw().len() as u32).to_le_bytes());
+            data.extend(img.as_raw());
+        }
+    }
+
+    Ok(data)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        if self.0.len() < len {
+            return Err(BinaryError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], BinaryError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn take_u32(&mut self) -> Result<u32, BinaryError> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+}
+
+/// The spritesheet info as it was before frame events were added. Bincode doesn't support
+/// missing fields, so it has to be read as this and converted.
+mod v1 {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::super::interface::{self, HitboxInfo, HitboxPosition};
+
+    #[derive(Deserialize)]
+    pub struct SpritesheetInfo {
+        cell_width: usize,
+        cell_height: usize,
+        columns: usize,
+        frame_count: usize,
+        frame_data: Vec<FrameData>,
+        hitboxes: HashMap<usize, HitboxInfo>,
+    }
+
+    #[derive(Deserialize)]
+    struct FrameData {
+        delay: usize,
+        origin: [f32; 2],
+        root_motion: [f32; 2],
+        hitboxes: HashMap<usize, HitboxPosition>,
+    }
+
+    impl From<SpritesheetInfo> for interface::SpritesheetInfo {
+        fn from(info: SpritesheetInfo) -> Self {
+            Self {
+                cell_width: info.cell_width,
+                cell_height: info.cell_height,
+                columns: info.columns,
+                frame_count: info.frame_count,
+                frame_data: info
+                    .frame_data
+                    .into_iter()
+                    .map(|fd| interface::FrameData {
+                        delay: fd.delay,
+                        origin: fd.origin,
+                        root_motion: fd.root_motion,
+                        hitboxes: fd.hitboxes,
+                        events: vec![],
+                    })
+                    .collect(),
+                hitboxes: info.hitboxes,
+                loop_mode: Default::default(),
+            }
+        }
+    }
+}
+
+/// The spritesheet info as it was before loop modes were added
+mod v2 {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::super::interface::{self, FrameData, HitboxInfo};
+
+    #[derive(Deserialize)]
+    pub struct SpritesheetInfo {
+        cell_width: usize,
+        cell_height: usize,
+        columns: usize,
+        frame_count: usize,
+        frame_data: Vec<FrameData>,
+        hitboxes: HashMap<usize, HitboxInfo>,
+    }
+
+    impl From<SpritesheetInfo> for interface::SpritesheetInfo {
+        fn from(info: SpritesheetInfo) -> Self {
+            Self {
+                cell_width: info.cell_width,
+                cell_height: info.cell_height,
+                columns: info.columns,
+                frame_count: info.frame_count,
+                frame_data: info.frame_data,
+                hitboxes: info.hitboxes,
+                loop_mode: Default::default(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::interface::Animation, super::LoopMode, *};
+
+    fn shipped() -> Animation {
+        let data = std::fs::read("assets/animations/c1_fireball_throw.anim").unwrap();
+        let mut anim: Animation = serde_json::from_slice(&data).unwrap();
+        anim.info.loop_mode = LoopMode::LoopFrom(2);
+        anim
+    }
+
+    /// Infos don't implement `PartialEq`, so they are compared in their JSON form
+    fn same_info(a: &SpritesheetInfo, b: &SpritesheetInfo) -> bool {
+        serde_json::to_value(a).unwrap() == serde_json::to_value(b).unwrap()
+    }
+
+    /// An animation in an older version, with the info already encoded in that version
+    fn with_version(version: u16, info: Vec<u8>, png: &[u8]) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend(version.to_le_bytes());
+        data.extend((info.len() as u32).to_le_bytes());
+        data.extend(info);
+        data.push(IMAGE_PNG);
+        data.extend((png.len() as u32).to_le_bytes());
+        data.extend(png);
+        data
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let anim = shipped();
+        let data = write(&BinaryAnimation {
+            info: shipped().info,
+            spritesheet: Spritesheet::Png(anim.spritesheet.clone()),
+        })
+        .unwrap();
+        assert!(is_binary(&data));
+
+        let read = read(&data).unwrap();
+        assert!(same_info(&read.info, &anim.info));
+        assert!(read.info.frame_data.iter().any(|fd| !fd.events.is_empty()));
+        assert!(matches!(read.spritesheet, Spritesheet::Png(png) if png == anim.spritesheet));
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let anim = shipped();
+        let img = image::load_from_memory(&anim.spritesheet)
+            .unwrap()
+            .into_rgba8();
+        let data = write(&BinaryAnimation {
+            info: shipped().info,
+            spritesheet: Spritesheet::Raw(img.clone()),
+        })
+        .unwrap();
+
+        let read = read(&data).unwrap();
+        assert!(same_info(&read.info, &anim.info));
+        assert!(matches!(read.spritesheet, Spritesheet::Raw(raw) if raw == img));
+    }
+
+    #[test]
+    fn reads_version_1_without_events_or_loop_mode() {
+        let anim = shipped();
+        let info = &anim.info;
+        // Bincode encodes structs like tuples of their fields
+        let frame_data: Vec<_> = info
+            .frame_data
+            .iter()
+            .map(|fd| (fd.delay, fd.origin, fd.root_motion, &fd.hitboxes))
+            .collect();
+        let v1 = (
+            info.cell_width,
+            info.cell_height,
+            info.columns,
+            info.frame_count,
+            frame_data,
+            &info.hitboxes,
+        );
+        let data = with_version(1, bincode::serialize(&v1).unwrap(), &anim.spritesheet);
+
+        let mut expected = shipped().info;
+        expected.loop_mode = LoopMode::default();
+        for fd in &mut expected.frame_data {
+            fd.events.clear();
+        }
+        assert!(same_info(&read(&data).unwrap().info, &expected));
+    }
+
+    #[test]
+    fn reads_version_2_without_loop_mode() {
+        let anim = shipped();
+        let info = &anim.info;
+        let v2 = (
+            info.cell_width,
+            info.cell_height,
+            info.columns,
+            info.frame_count,
+            &info.frame_data,
+            &info.hitboxes,
+        );
+        let data = with_version(2, bincode::serialize(&v2).unwrap(), &anim.spritesheet);
+
+        let read = read(&data).unwrap().info;
+        assert_eq!(read.loop_mode, LoopMode::default());
+        let mut expected = shipped().info;
+        expected.loop_mode = LoopMode::default();
+        assert!(same_info(&read, &expected));
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let data = write(&BinaryAnimation {
+            info: shipped().info,
+            spritesheet: Spritesheet::Png(shipped().spritesheet),
+        })
+        .unwrap();
+        assert!(matches!(
+            read(&data[..data.len() - 1]),
+            Err(BinaryError::Truncated)
+        ));
+
+        let mut future = data.clone();
+        future[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            read(&future),
+            Err(BinaryError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, GenericImageView, Pixel, Rgba, RgbaImage,
+};
+
+use super::{
+    super::{hud, FPS},
+    cell_position, read, AnimationError, AnimationErrorKind,
+};
+
+const HURTBOX_COLOR: Rgba<u8> = Rgba([0, 255, 0, 120]);
+const HITBOX_COLOR: Rgba<u8> = Rgba([255, 0, 0, 120]);
+const FRAME_NUMBER_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+/// GIFs have no partial transparency, so their frames are drawn on this instead
+const GIF_BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// What to draw when rendering an animation to images
+pub struct ExportOptions {
+    pub hitboxes: bool,
+    /// Draws the index of each frame in its top left corner
+    pub frame_numbers: bool,
+    /// Size of a pixel of the spritesheet in the output
+    pub scale: u32,
+}
+
+/// Renders every frame of an animation. The frames all have the same size, with the origin at
+/// the same position, so they line up when played back. Root motion is left out, so the character
+/// stays in place. Returns the images along with the delay of each frame.
+fn render(
+    path: &Path,
+    options: &ExportOptions,
+) -> Result<Vec<(RgbaImage, usize)>, AnimationError> {
+    let (info, img_data) = read(path)?;
+
+    // Boxes relative to the origin of each frame: the cell, then the enabled hitboxes along with
+    // whether they are hurtboxes
+    let mut frames = vec![];
+    for (frame, fd) in info.frame_data.iter().enumerate() {
+        let cell = cell_position(&info, &img_data, frame).ok_or(AnimationError {
+            path: path.to_path_buf(),
+            kind: AnimationErrorKind::CellOutOfBounds { frame },
+        })?;
+
+        let mut boxes = vec![];
+        if options.hitboxes {
+            for hp in fd.hitboxes.values().filter(|hp| hp.enabled) {
+                let Some(hitbox) = info.hitboxes.get(&hp.id) else {
+                    return Err(AnimationError {
+                        path: path.to_path_buf(),
+                        kind: AnimationErrorKind::UnknownHitbox {
+                            frame,
+                            hitbox: hp.id,
+                        },
+                    });
+                };
+                let min = [hp.pos[0], -hp.pos[1]];
+                let max = [min[0] + hp.size[0], min[1] + hp.size[1]];
+                boxes.push((min, max, hitbox.is_hurtbox));
+            }
+            // Hitboxes usually overlap hurtboxes, so they are drawn on top to stay visible
+            boxes.sort_by_key(|&(_, _, is_hurtbox)| !is_hurtbox);
+        }
+        frames.push((cell, fd.origin, boxes));
+    }
+
+    if frames.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Everything that is drawn on any frame has to fit
+    let mut min = [f32::INFINITY; 2];
+    let mut max = [f32::NEG_INFINITY; 2];
+    for (_, origin, boxes) in &frames {
+        let cell_min = [-origin[0], -origin[1]];
+        let cell_max = [
+            cell_min[0] + info.cell_width as f32,
+            cell_min[1] + info.cell_height as f32,
+        ];
+        let corners = boxes.iter().map(|&(min, max, _)| (min, max));
+        for (box_min, box_max) in std::iter::once((cell_min, cell_max)).chain(corners) {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(box_min[axis]);
+                max[axis] = max[axis].max(box_max[axis]);
+            }
+        }
+    }
+    let min = [min[0].floor(), min[1].floor()];
+    let width = (max[0].ceil() - min[0]) as u32;
+    let height = (max[1].ceil() - min[1]) as u32;
+
+    let images = frames
+        .into_iter()
+        .zip(&info.frame_data)
+        .enumerate()
+        .map(|(frame, (((x, y), origin, boxes), fd))| {
+            let mut image = RgbaImage::new(width, height);
+
+            let cell = img_data.view(x, y, info.cell_width as u32, info.cell_height as u32);
+            imageops::overlay(
+                &mut image,
+                &*cell,
+                (-origin[0] - min[0]).round() as i64,
+                (-origin[1] - min[1]).round() as i64,
+            );
+
+            for (box_min, box_max, is_hurtbox) in boxes {
+                let color = if is_hurtbox {
+                    HURTBOX_COLOR
+                } else {
+                    HITBOX_COLOR
+                };
+                let x = (box_min[0] - min[0]).round() as u32;
+                let y = (box_min[1] - min[1]).round() as u32;
+                let x_end = ((box_max[0] - min[0]).round() as u32).min(width);
+                let y_end = ((box_max[1] - min[1]).round() as u32).min(height);
+                // Filled see-through, with a solid outline
+                for py in y..y_end {
+                    for px in x..x_end {
+                        if px == x || py == y || px + 1 == x_end || py + 1 == y_end {
+                            image.put_pixel(px, py, Rgba([color[0], color[1], color[2], 255]));
+                        } else {
+                            image.get_pixel_mut(px, py).blend(&color);
+                        }
+                    }
+                }
+            }
+
+            // Scaled before the frame number is drawn, so the number stays sharp
+            let scale = options.scale.max(1);
+            let mut image =
+                imageops::resize(&image, width * scale, height * scale, FilterType::Nearest);
+
+            if options.frame_numbers {
+                let margin = 2 * scale as i32;
+                let (segments, _) = hud::number_segments(margin, margin, 10 * scale, frame);
+                for segment in segments {
+                    for py in segment.top()..segment.bottom() {
+                        for px in segment.left()..segment.right() {
+                            let (px, py) = (px as u32, py as u32);
+                            if px < image.width() && py < image.height() {
+                                image.put_pixel(px, py, FRAME_NUMBER_COLOR);
+                            }
+                        }
+                    }
+                }
+            }
+
+            (image, fd.delay)
+        })
+        .collect();
+
+    Ok(images)
+}
+
+/// Renders an animation to an animated GIF if `output` ends in `.gif`, and otherwise to a PNG
+/// for every frame in the `output` directory
+pub fn export(
+    input: &Path,
+    output: &Path,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frames = render(input, options)?;
+
+    if output.extension().is_some_and(|ext| ext == "gif") {
+        if let Some(dir) = output.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut encoder = GifEncoder::new(BufWriter::new(File::create(output)?));
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.into_iter().map(|(image, delay)| {
+            let mut background =
+                RgbaImage::from_pixel(image.width(), image.height(), GIF_BACKGROUND);
+            imageops::overlay(&mut background, &image, 0, 0);
+            let delay = Delay::from_numer_denom_ms((delay * 1000) as u32, FPS as u32);
+            image::Frame::from_parts(background, 0, 0, delay)
+        }))?;
+    } else {
+        std::fs::create_dir_all(output)?;
+        for (frame, (image, _)) in frames.iter().enumerate() {
+            image.save(output.join(format!("frame_{frame:03}.png")))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        super::binary::{self, BinaryAnimation, Spritesheet},
+        *,
+    };
+
+    const CELL_COLOR: Rgba<u8> = Rgba([200, 100, 50, 255]);
+
+    /// Two opaque 10x10 frames. The second one has its origin further right and a hitbox past
+    /// the right edge of its cell.
+    fn write_fixture(name: &str) -> std::path::PathBuf {
+        let info = json!({
+            "cell_width": 10,
+            "cell_height": 10,
+            "columns": 2,
+            "frame_count": 2,
+            "frame_data": [
+                {
+                    "delay": 2,
+                    "origin": [5.0, 10.0],
+                    "root_motion": [0.0, 0.0],
+                    "hitboxes": {},
+                },
+                {
+                    "delay": 4,
+                    "origin": [7.0, 10.0],
+                    "root_motion": [0.0, 0.0],
+                    "hitboxes": {
+                        "0": { "id": 0, "pos": [6.0, 4.0], "size": [4.0, 2.0], "enabled": true },
+                    },
+                },
+            ],
+            "hitboxes": {
+                "0": { "id": 0, "desc": "fist", "is_hurtbox": false },
+            },
+        });
+        let data = binary::write(&BinaryAnimation {
+            info: serde_json::from_value(info).unwrap(),
+            spritesheet: Spritesheet::Raw(RgbaImage::from_pixel(20, 10, CELL_COLOR)),
+        })
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("fg-sdl2-export-{name}.fganim"));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn render_fixture(name: &str, options: ExportOptions) -> Vec<(RgbaImage, usize)> {
+        let path = write_fixture(name);
+        let frames = render(&path, &options).unwrap();
+        std::fs::remove_file(path).unwrap();
+        frames
+    }
+
+    fn options(hitboxes: bool, frame_numbers: bool, scale: u32) -> ExportOptions {
+        ExportOptions {
+            hitboxes,
+            frame_numbers,
+            scale,
+        }
+    }
+
+    #[test]
+    fn frames_fit_every_cell_and_line_up_on_the_origin() {
+        let frames = render_fixture("cells", options(false, false, 1));
+
+        // The cells span from 7 left of the origin to 5 right of it
+        let sizes: Vec<_> = frames.iter().map(|(image, _)| image.dimensions()).collect();
+        assert_eq!(sizes, [(12, 10), (12, 10)]);
+        assert_eq!(frames[0].0.get_pixel(1, 0)[3], 0);
+        assert_eq!(*frames[0].0.get_pixel(2, 0), CELL_COLOR);
+        assert_eq!(*frames[1].0.get_pixel(9, 0), CELL_COLOR);
+        assert_eq!(frames[1].0.get_pixel(10, 0)[3], 0);
+        assert_eq!(
+            frames.iter().map(|&(_, delay)| delay).collect::<Vec<_>>(),
+            [2, 4]
+        );
+    }
+
+    #[test]
+    fn frames_grow_to_fit_hitboxes() {
+        let frames = render_fixture("hitboxes", options(true, false, 1));
+
+        assert_eq!(frames[0].0.dimensions(), (17, 10));
+        // The hitbox goes from 6 to 10 right of the origin, and 4 to 2 above it
+        let outline = Rgba([HITBOX_COLOR[0], HITBOX_COLOR[1], HITBOX_COLOR[2], 255]);
+        assert_eq!(*frames[1].0.get_pixel(13, 6), outline);
+        assert_eq!(*frames[1].0.get_pixel(16, 7), outline);
+        assert_eq!(frames[0].0.get_pixel(13, 6)[3], 0);
+    }
+
+    #[test]
+    fn scale_applies_to_the_whole_frame() {
+        let frames = render_fixture("scale", options(true, false, 3));
+        assert_eq!(frames[0].0.dimensions(), (17 * 3, 10 * 3));
+    }
+
+    #[test]
+    fn frame_numbers_are_drawn_on_top() {
+        let has_number = |frames: &[(RgbaImage, usize)]| {
+            frames[0]
+                .0
+                .pixels()
+                .any(|&pixel| pixel == FRAME_NUMBER_COLOR)
+        };
+        assert!(!has_number(&render_fixture(
+            "no-numbers",
+            options(false, false, 4)
+        )));
+        assert!(has_number(&render_fixture(
+            "numbers",
+            options(false, true, 4)
+        )));
+    }
+}
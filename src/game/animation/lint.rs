@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use image::RgbaImage;
+
+use super::{attack_phases, interface, read, to_fixed, LoopMode};
+
+/// What was found when checking an animation file
+pub struct LintReport {
+    pub problems: Vec<String>,
+    /// Startup, active frames and recovery, if the file could be read
+    pub phases: Option<(usize, usize, usize)>,
+}
+
+/// Checks an animation file for data that would make it fail to load or misbehave at runtime.
+/// Unlike `Animation::load`, this reports every problem instead of stopping at the first one,
+/// and doesn't need a window to create textures with.
+pub fn lint(path: &Path) -> LintReport {
+    match read(path) {
+        Ok((info, img_data)) => check(&info, &img_data),
+        Err(e) => LintReport {
+            problems: vec![e.to_string()],
+            phases: None,
+        },
+    }
+}
+
+fn check(info: &interface::SpritesheetInfo, img_data: &RgbaImage) -> LintReport {
+    let mut problems = vec![];
+
+    if info.frame_count != info.frame_data.len() {
+        problems.push(format!(
+            "frame_count is {}, but there are {} frames",
+            info.frame_count,
+            info.frame_data.len()
+        ));
+    }
+    if info.columns == 0 {
+        problems.push("columns is 0".into());
+    }
+    if info.cell_width == 0 || info.cell_height == 0 {
+        problems.push(format!(
+            "cells are {}x{}",
+            info.cell_width, info.cell_height
+        ));
+    }
+
+    if let LoopMode::LoopFrom(frame) = info.loop_mode {
+        if frame >= info.frame_data.len() {
+            problems.push(format!("loops from frame {frame}, which doesn't exist"));
+        }
+    }
+
+    for (id, hitbox) in &info.hitboxes {
+        if hitbox.id != *id {
+            problems.push(format!("hitbox {id} has the id {}", hitbox.id));
+        }
+    }
+
+    for (frame, fd) in info.frame_data.iter().enumerate() {
+        if fd.delay == 0 {
+            problems.push(format!("frame {frame}: delay is 0"));
+        }
+
+        if info.columns > 0 {
+            let right = (frame % info.columns + 1) * info.cell_width;
+            let bottom = (frame / info.columns + 1) * info.cell_height;
+            if right > img_data.width() as usize || bottom > img_data.height() as usize {
+                problems.push(format!(
+                    "frame {frame}: cell ends at {right}x{bottom}, outside the {}x{} spritesheet",
+                    img_data.width(),
+                    img_data.height()
+                ));
+            }
+        }
+
+        for (field, [x, y]) in [("origin", fd.origin), ("root_motion", fd.root_motion)] {
+            for value in [x, y] {
+                if to_fixed(value).is_none() {
+                    problems.push(format!("frame {frame}: invalid {field} value {value}"));
+                }
+            }
+        }
+
+        for event in &fd.events {
+            if let interface::FrameEvent::SpawnEffect { offset, .. } = event {
+                for value in offset {
+                    if to_fixed(*value).is_none() {
+                        problems.push(format!(
+                            "frame {frame}: invalid effect offset value {value}"
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (id, hp) in &fd.hitboxes {
+            if !info.hitboxes.contains_key(id) {
+                problems.push(format!("frame {frame}, hitbox {id}: hitbox isn't declared"));
+            }
+            for (field, [x, y]) in [("pos", hp.pos), ("size", hp.size)] {
+                for value in [x, y] {
+                    if to_fixed(value).is_none() {
+                        problems.push(format!(
+                            "frame {frame}, hitbox {id}: invalid {field} value {value}"
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let phases = attack_phases(info.frame_data.iter().map(|fd| {
+        let has_hitbox = fd.hitboxes.iter().any(|(id, hp)| {
+            hp.enabled && info.hitboxes.get(id).is_some_and(|info| !info.is_hurtbox)
+        });
+        (fd.delay, has_hitbox)
+    }));
+
+    LintReport {
+        problems,
+        phases: Some(phases),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Two 16x16 frames side by side, with a hurtbox and a hitbox
+    fn info() -> serde_json::Value {
+        let frame = || {
+            json!({
+                "delay": 3,
+                "origin": [8.0, 16.0],
+                "root_motion": [0.0, 0.0],
+                "hitboxes": {
+                    "0": { "id": 0, "pos": [-4.0, 16.0], "size": [8.0, 16.0], "enabled": true },
+                    "1": { "id": 1, "pos": [4.0, 12.0], "size": [6.0, 4.0], "enabled": true },
+                },
+            })
+        };
+        json!({
+            "cell_width": 16,
+            "cell_height": 16,
+            "columns": 2,
+            "frame_count": 2,
+            "frame_data": [frame(), frame()],
+            "hitboxes": {
+                "0": { "id": 0, "desc": "body", "is_hurtbox": true },
+                "1": { "id": 1, "desc": "fist", "is_hurtbox": false },
+            },
+        })
+    }
+
+    fn check_json(info: serde_json::Value, width: u32, height: u32) -> LintReport {
+        let info: interface::SpritesheetInfo = serde_json::from_value(info).unwrap();
+        check(&info, &RgbaImage::new(width, height))
+    }
+
+    #[test]
+    fn valid_animation_has_no_problems() {
+        let report = check_json(info(), 32, 16);
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!(report.phases, Some((0, 6, 0)));
+    }
+
+    #[test]
+    fn undeclared_hitbox() {
+        let mut info = info();
+        info["frame_data"][1]["hitboxes"]["2"] =
+            json!({ "id": 2, "pos": [0.0, 0.0], "size": [1.0, 1.0], "enabled": true });
+
+        let report = check_json(info, 32, 16);
+        assert_eq!(
+            report.problems,
+            ["frame 1, hitbox 2: hitbox isn't declared"]
+        );
+    }
+
+    #[test]
+    fn frame_count_mismatch() {
+        let mut info = info();
+        info["frame_count"] = json!(3);
+
+        let report = check_json(info, 32, 16);
+        assert_eq!(
+            report.problems,
+            ["frame_count is 3, but there are 2 frames"]
+        );
+    }
+
+    #[test]
+    fn cell_out_of_bounds() {
+        let report = check_json(info(), 24, 16);
+        assert_eq!(
+            report.problems,
+            ["frame 1: cell ends at 32x16, outside the 24x16 spritesheet"]
+        );
+    }
+}
@@ -1,53 +1,278 @@
 mod guy;
+mod script;
+mod stats;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, panic::Location};
 
 use macros::CharacterStateContainer;
 use sdl2::{render::TextureCreator, video::WindowContext};
+use serde::{Deserialize, Serialize};
 
 use super::{fixed_point::FixedPoint, input::InputDirection, time::Frame};
 
-use super::{animation::Animation, movelist::Movelist, GameInfo, GameState, PlayerSide};
+pub use script::ScriptedState;
+pub use stats::CharacterStats;
+
+use script::{CharacterScript, StateDefinition};
+
+use super::{
+    animation::{
+        Animation, AnimationError, AnimationErrorKind, AnimationId, AnimationStore, FrameEvent,
+    },
+    data_error::DataError,
+    movelist::Movelist,
+    palette::Palette,
+    projectile::ProjectileSpawn,
+    GameInfo, GameState, PlayerSide, MAX_GUARD,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Character {
     Guy,
+    Dude,
+}
+
+impl Character {
+    /// Every character, in the order they are shown on the character select screen
+    pub const ALL: [Character; 2] = [Character::Guy, Character::Dude];
+}
+
+/// A character picked by a player, along with the palette to draw it with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharacterChoice {
+    pub character: Character,
+    pub palette: usize,
+}
+
+impl CharacterChoice {
+    /// Moves to the next palette if `other` would otherwise look exactly the same
+    pub fn distinct_from(self, other: CharacterChoice, palette_count: usize) -> Self {
+        if self == other {
+            Self {
+                palette: (self.palette + 1) % palette_count,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
 }
 
 pub struct CharacterProto {
     pub name: String,
-    pub animations: HashMap<&'static str, &'static Animation>,
+    pub animations: HashMap<&'static str, AnimationId>,
     pub movelist: Movelist,
+    /// Projectiles that animations can spawn by name
+    pub projectiles: HashMap<&'static str, ProjectileSpawn>,
+    pub stats: CharacterStats,
+    pub palettes: Vec<Palette>,
+    /// Indexed by `ScriptedState::id`
+    pub scripted_states: Vec<StateDefinition>,
+    pub stats_path: &'static str,
+    /// Script with the moves and states of scripted characters
+    pub script_path: Option<&'static str>,
 }
 
 impl CharacterProto {
-    pub fn create_guy(texture_creator: &'static TextureCreator<WindowContext>) -> Self {
-        Self {
+    /// Gets an animation, falling back to another one if the character doesn't have it
+    pub fn animation_or(&self, name: &str, fallback: &str) -> AnimationId {
+        self.animations
+            .get(name)
+            .copied()
+            .unwrap_or_else(|| self.animations[fallback])
+    }
+
+    /// Loads the stats and, for scripted characters, the moves and states again. Nothing is
+    /// changed if any of them fails to load.
+    pub fn reload_data(&mut self) -> Result<(), DataError> {
+        let stats = CharacterStats::load(self.stats_path)?;
+        let script = self
+            .script_path
+            .map(|path| CharacterScript::load(path, &self.animations))
+            .transpose()?;
+
+        self.stats = stats;
+        if let Some(script) = script {
+            self.movelist = script.movelist;
+            self.scripted_states = script.states;
+        }
+        Ok(())
+    }
+}
+
+/// Why a character couldn't be loaded
+#[derive(Debug)]
+pub enum CharacterError {
+    Animation(AnimationError),
+    Data(DataError),
+}
+
+impl From<AnimationError> for CharacterError {
+    fn from(e: AnimationError) -> Self {
+        Self::Animation(e)
+    }
+}
+
+impl From<DataError> for CharacterError {
+    fn from(e: DataError) -> Self {
+        Self::Data(e)
+    }
+}
+
+impl Display for CharacterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CharacterError::Animation(e) => e.fmt(f),
+            CharacterError::Data(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CharacterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CharacterError::Animation(e) => e.source(),
+            CharacterError::Data(e) => e.source(),
+        }
+    }
+}
+
+impl CharacterProto {
+    pub fn create(
+        character: Character,
+        store: &mut AnimationStore,
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Self, CharacterError> {
+        let proto = match character {
+            Character::Guy => Self::create_guy(store, texture_creator),
+            Character::Dude => Self::create_dude(store, texture_creator),
+        }?;
+        for &id in proto.animations.values() {
+            proto
+                .check_frame_events(&store[id])
+                .map_err(|kind| AnimationError {
+                    path: store.path(id).to_path_buf(),
+                    kind,
+                })?;
+        }
+        Ok(proto)
+    }
+
+    /// Checks that the frame events of one of the character's animations only spawn effects and
+    /// projectiles the character has
+    pub fn check_frame_events(&self, animation: &Animation) -> Result<(), AnimationErrorKind> {
+        for (frame, frame_data) in animation.frame_data.iter().enumerate() {
+            for event in &frame_data.events {
+                match event {
+                    FrameEvent::SpawnEffect { animation, .. }
+                        if !self.animations.contains_key(animation.as_str()) =>
+                    {
+                        return Err(AnimationErrorKind::UnknownEffect {
+                            frame,
+                            animation: animation.clone(),
+                        });
+                    }
+                    FrameEvent::SpawnProjectile(projectile)
+                        if !self.projectiles.contains_key(projectile.as_str()) =>
+                    {
+                        return Err(AnimationErrorKind::UnknownProjectile {
+                            frame,
+                            projectile: projectile.clone(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn create_guy(
+        store: &mut AnimationStore,
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Self, CharacterError> {
+        let palettes = Palette::load_all("assets/palettes/guy.json")?;
+        let stats_path = "assets/characters/guy.json";
+        Ok(Self {
             name: "Guy".into(),
-            animations: [
-                (
-                    "idle",
-                    Animation::load("assets/animations/c1_idle.anim", texture_creator).unwrap(),
-                ),
-                (
-                    "walking",
-                    Animation::load("assets/animations/c1_walking_v2.anim", texture_creator)
-                        .unwrap(),
-                ),
-                (
-                    "punch",
-                    Animation::load("assets/animations/c1_punch.anim", texture_creator).unwrap(),
-                ),
-                (
-                    "hitstun",
-                    Animation::load("assets/animations/c1_hitstun.anim", texture_creator).unwrap(),
-                ),
-            ]
-            .into_iter()
-            .collect(),
+            animations: load_animations(
+                &[
+                    ("idle", "assets/animations/c1_idle.anim"),
+                    ("walking", "assets/animations/c1_walking_v2.anim"),
+                    ("dash", "assets/animations/c1_dash.anim"),
+                    ("run", "assets/animations/c1_run.anim"),
+                    ("backdash", "assets/animations/c1_backdash.anim"),
+                    ("jump_squat", "assets/animations/c1_jump_squat.anim"),
+                    ("air_dash", "assets/animations/c1_air_dash.anim"),
+                    ("punch", "assets/animations/c1_punch.anim"),
+                    ("hitstun", "assets/animations/c1_hitstun.anim"),
+                    ("fireball", "assets/animations/c1_fireball.anim"),
+                    ("big_punch", "assets/animations/c1_big_punch.anim"),
+                    ("fireball_throw", "assets/animations/c1_fireball_throw.anim"),
+                ],
+                &palettes,
+                store,
+                texture_creator,
+            )?,
             movelist: guy::movelist(),
-        }
+            projectiles: HashMap::from([("fireball", guy::fireball())]),
+            stats: CharacterStats::load(stats_path)?,
+            palettes,
+            scripted_states: vec![],
+            stats_path,
+            script_path: None,
+        })
     }
+
+    /// Dude is a bigger, heavier build of Guy with his own animations, stats and scripted moves
+    pub fn create_dude(
+        store: &mut AnimationStore,
+        texture_creator: Option<&TextureCreator<WindowContext>>,
+    ) -> Result<Self, CharacterError> {
+        let palettes = Palette::load_all("assets/palettes/dude.json")?;
+        let stats_path = "assets/characters/dude.json";
+        let script_path = "assets/characters/dude_script.json";
+        let animations = load_animations(
+            &[
+                ("idle", "assets/animations/c2_idle.anim"),
+                ("walking", "assets/animations/c2_walking.anim"),
+                ("dash", "assets/animations/c2_dash.anim"),
+                ("run", "assets/animations/c2_run.anim"),
+                ("backdash", "assets/animations/c2_backdash.anim"),
+                ("jump_squat", "assets/animations/c2_jump_squat.anim"),
+                ("air_dash", "assets/animations/c2_air_dash.anim"),
+                ("punch", "assets/animations/c2_punch.anim"),
+                ("roundhouse", "assets/animations/c2_roundhouse.anim"),
+                ("hitstun", "assets/animations/c2_hitstun.anim"),
+            ],
+            &palettes,
+            store,
+            texture_creator,
+        )?;
+        let script = CharacterScript::load(script_path, &animations)?;
+        Ok(Self {
+            name: "Dude".into(),
+            animations,
+            movelist: script.movelist,
+            projectiles: HashMap::new(),
+            stats: CharacterStats::load(stats_path)?,
+            palettes,
+            scripted_states: script.states,
+            stats_path,
+            script_path: Some(script_path),
+        })
+    }
+}
+
+fn load_animations(
+    paths: &[(&'static str, &str)],
+    palettes: &[Palette],
+    store: &mut AnimationStore,
+    texture_creator: Option<&TextureCreator<WindowContext>>,
+) -> Result<HashMap<&'static str, AnimationId>, AnimationError> {
+    let files: Vec<&str> = paths.iter().map(|&(_, path)| path).collect();
+    let ids = store.load_group(&files, palettes, texture_creator)?;
+    Ok(paths.iter().map(|&(name, _)| name).zip(ids).collect())
 }
 
 pub trait State: Sized {
@@ -113,28 +338,38 @@ impl StateTransitionRequests {
 pub struct StateTransitionRequest {
     pub state: CharacterState,
     pub insert_priority: usize,
+    /// Where the request was made, for the event log
+    pub requester: &'static Location<'static>,
 }
 
 impl StateTransitionRequest {
+    #[track_caller]
     pub fn new(state: CharacterState, insert_priority: impl Into<Option<usize>>) -> Self {
         let insert_priority = insert_priority.into().unwrap_or_else(|| state.priority());
         Self {
             state,
             insert_priority,
+            requester: Location::caller(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer)]
+#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer, Hash, Serialize, Deserialize)]
 pub enum CharacterState {
     Idle(IdleState),
+    Dash(DashState),
+    Run(RunState),
+    JumpSquat(JumpSquatState),
     Airborne(AirborneState),
+    AirDash(AirDashState),
     Blockstun(BlockstunState),
     Hitstun(HitstunState),
+    Crumple(CrumpleState),
+    GuardCrush(GuardCrushState),
     CharacterSpecific(CharacterSpecificState),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct IdleState;
 
 impl State for IdleState {
@@ -146,6 +381,8 @@ impl State for IdleState {
             let frame = state.current_frame;
             let player = state.player_mut(player);
             player.velocity.x = FixedPoint::ZERO;
+            player.wall_bounce = None;
+            player.ground_bounce = None;
             player.animator.switch_animation(
                 frame,
                 info.character_protos[&player.character].animations["idle"],
@@ -161,11 +398,41 @@ impl State for IdleState {
     ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
         Some(Box::new(move |state, info| {
             let player = state.player_mut(player);
+            let stats = &info.character_protos[&player.character].stats;
             let dir = player.input_history.iter(0, frame).input_dir_as_of_here();
 
+            let (forward, backward) = match player.facing {
+                Facing::Left => (InputDirection::Left, InputDirection::Right),
+                Facing::Right => (InputDirection::Right, InputDirection::Left),
+            };
+            let dash = if player.input_history.double_tap(forward, DASH_WINDOW, frame) {
+                Some(DashDirection::Forward)
+            } else if player.input_history.double_tap(backward, DASH_WINDOW, frame) {
+                Some(DashDirection::Backward)
+            } else {
+                None
+            };
+            if let Some(direction) = dash {
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(
+                        DashState { direction, frame: 0 }.wrap(),
+                        None,
+                    ));
+                return;
+            }
+
+            let walk_speed = |dir| {
+                if dir == forward {
+                    stats.walk_speed
+                } else {
+                    stats.back_walk_speed
+                }
+            };
+
             match dir {
                 InputDirection::Right => {
-                    player.velocity.x = FixedPoint::from(60.0);
+                    player.velocity.x = walk_speed(dir);
                     player.animator.make_sure_animation(
                         frame,
                         info.character_protos[&player.character].animations["walking"],
@@ -173,7 +440,7 @@ impl State for IdleState {
                     );
                 }
                 InputDirection::Left => {
-                    player.velocity.x = FixedPoint::from(-60.0);
+                    player.velocity.x = -walk_speed(dir);
                     player.animator.make_sure_animation(
                         frame,
                         info.character_protos[&player.character].animations["walking"],
@@ -188,65 +455,414 @@ impl State for IdleState {
                         None,
                     );
                 }
-                InputDirection::Up => {
-                    player.velocity.y = -FixedPoint::from(200);
+                InputDirection::Up | InputDirection::UpRight | InputDirection::UpLeft => {
+                    let direction = JumpDirection::from_input_dir(dir).unwrap();
+                    let kind = if player.input_history.down_up(SUPER_JUMP_WINDOW, frame) {
+                        JumpKind::Super
+                    } else {
+                        JumpKind::Normal
+                    };
                     player
                         .state_transition_requests
                         .add(StateTransitionRequest::new(
-                            AirborneState(JumpDirection::Up).wrap(),
+                            JumpSquatState {
+                                direction,
+                                kind,
+                                frame: 0,
+                            }
+                            .wrap(),
                             None,
                         ));
                 }
-                InputDirection::UpRight => {
-                    player.velocity.y = -FixedPoint::from(200);
+                _ => {}
+            }
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        0
+    }
+}
+
+/// Frames within which both taps of a dash have to happen
+pub const DASH_WINDOW: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DashDirection {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DashState {
+    pub direction: DashDirection,
+    /// Frames since the dash started
+    pub frame: usize,
+}
+
+impl State for DashState {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let direction = self.direction;
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            let proto = &info.character_protos[&player.character];
+            let animation = match direction {
+                DashDirection::Forward => proto.animations["dash"],
+                DashDirection::Backward => proto.animations["backdash"],
+            };
+            player.animator.switch_animation(frame, animation, None);
+        }))
+    }
+
+    fn pre_tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let direction = self.direction;
+        let dash_frame = self.frame;
+        self.frame += 1;
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let stats = &info.character_protos[&player.character].stats;
+            let (speed, duration) = match direction {
+                DashDirection::Forward => (stats.dash_speed, stats.dash_frames),
+                DashDirection::Backward => (-stats.backdash_speed, stats.backdash_frames),
+            };
+
+            if dash_frame >= duration {
+                let dir = player.input_history.iter(0, frame).input_dir_as_of_here();
+                let running =
+                    direction == DashDirection::Forward && dir == forward_direction(player.facing);
+                let next = if running {
+                    RunState.wrap()
+                } else {
+                    IdleState.wrap()
+                };
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(next, usize::MAX));
+                return;
+            }
+
+            player.velocity.x = match player.facing {
+                Facing::Left => -speed,
+                Facing::Right => speed,
+            };
+            player.invincible = direction == DashDirection::Backward
+                && dash_frame < stats.backdash_invincibility;
+        }))
+    }
+
+    fn on_exit(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, _| {
+            state.player_mut(player).invincible = false;
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        500
+    }
+}
+
+/// The input direction that points the way the player is facing
+fn forward_direction(facing: Facing) -> InputDirection {
+    match facing {
+        Facing::Left => InputDirection::Left,
+        Facing::Right => InputDirection::Right,
+    }
+}
+
+/// Keeps going after a forward dash for as long as forward is held
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RunState;
+
+impl State for RunState {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            let animation = info.character_protos[&player.character].animations["run"];
+            player.animator.switch_animation(frame, animation, None);
+        }))
+    }
+
+    fn tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let dir = player.input_history.iter(0, frame).input_dir_as_of_here();
+            if dir != forward_direction(player.facing) {
+                // Idle takes over from here, including jumping out of the run
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(IdleState.wrap(), usize::MAX));
+                return;
+            }
+
+            let speed = info.character_protos[&player.character].stats.run_speed;
+            player.velocity.x = match player.facing {
+                Facing::Left => -speed,
+                Facing::Right => speed,
+            };
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JumpDirection {
+    Left,
+    Up,
+    Right,
+}
+
+impl JumpDirection {
+    pub fn from_input_dir(dir: InputDirection) -> Option<Self> {
+        match dir {
+            InputDirection::UpLeft => Some(JumpDirection::Left),
+            InputDirection::Up => Some(JumpDirection::Up),
+            InputDirection::UpRight => Some(JumpDirection::Right),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JumpKind {
+    Normal,
+    Super,
+    Double,
+}
+
+/// Frames within which down has to be pressed before up for a super jump
+pub const SUPER_JUMP_WINDOW: usize = 10;
+
+/// The frames on the ground before a jump, during which the direction can still be changed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JumpSquatState {
+    pub direction: JumpDirection,
+    pub kind: JumpKind,
+    /// Frames since the jump squat started
+    pub frame: usize,
+}
+
+impl State for JumpSquatState {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            let animation = info.character_protos[&player.character].animations["jump_squat"];
+            player.velocity.x = FixedPoint::ZERO;
+            player.animator.switch_animation(frame, animation, None);
+        }))
+    }
+
+    fn tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let squat_frame = self.frame;
+        self.frame += 1;
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let stats = &info.character_protos[&player.character].stats;
+
+            let CharacterState::JumpSquat(squat) = &mut player.current_state else {
+                return;
+            };
+            let dir = player.input_history.iter(0, frame).input_dir_as_of_here();
+            if let Some(direction) = JumpDirection::from_input_dir(dir) {
+                squat.direction = direction;
+            }
+
+            if squat_frame + 1 >= stats.jump_squat_frames {
+                let (direction, kind) = (squat.direction, squat.kind);
+                player.velocity.y = match kind {
+                    JumpKind::Super => -stats.super_jump_velocity,
+                    _ => -stats.jump_velocity,
+                };
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(
+                        AirborneState(direction, kind).wrap(),
+                        None,
+                    ));
+            }
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        500
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AirborneState(pub JumpDirection, pub JumpKind);
+
+impl State for AirborneState {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let dir = self.0;
+        let kind = self.1;
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            let proto = &info.character_protos[&player.character];
+            let jump_speed = proto.stats.jump_speed;
+            player.velocity.x = match dir {
+                JumpDirection::Left => -jump_speed,
+                JumpDirection::Up => FixedPoint::ZERO,
+                JumpDirection::Right => jump_speed,
+            };
+
+            let animation = match kind {
+                JumpKind::Normal => proto.animation_or("jump", "idle"),
+                JumpKind::Super => proto.animation_or("super_jump", "idle"),
+                JumpKind::Double => proto.animation_or("double_jump", "idle"),
+            };
+            player.animator.make_sure_animation(frame, animation, None);
+        }))
+    }
+
+    fn tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let stats = &info.character_protos[&player.character].stats;
+
+            if stats.air_dash_frames > 0 && !player.air_dash_used {
+                let (forward, backward) = match player.facing {
+                    Facing::Left => (InputDirection::Left, InputDirection::Right),
+                    Facing::Right => (InputDirection::Right, InputDirection::Left),
+                };
+                let dash = if player.input_history.double_tap(forward, DASH_WINDOW, frame) {
+                    Some(DashDirection::Forward)
+                } else if player.input_history.double_tap(backward, DASH_WINDOW, frame) {
+                    Some(DashDirection::Backward)
+                } else {
+                    None
+                };
+                if let Some(direction) = dash {
+                    player.air_dash_used = true;
+                    // Same priority as being airborne, so it has to be forced like air jumps
                     player
                         .state_transition_requests
                         .add(StateTransitionRequest::new(
-                            AirborneState(JumpDirection::Right).wrap(),
-                            None,
+                            AirDashState { direction, frame: 0 }.wrap(),
+                            usize::MAX,
                         ));
+                    return;
                 }
-                InputDirection::UpLeft => {
-                    player.velocity.y = -FixedPoint::from(200);
+            }
+
+            if player.air_jumps_left > 0 {
+                let direction = player
+                    .input_history
+                    .direction_pressed_now(frame)
+                    .and_then(JumpDirection::from_input_dir);
+                if let Some(direction) = direction {
+                    player.air_jumps_left -= 1;
+                    player.velocity.y = -stats.jump_velocity;
                     player
                         .state_transition_requests
                         .add(StateTransitionRequest::new(
-                            AirborneState(JumpDirection::Left).wrap(),
-                            None,
+                            AirborneState(direction, JumpKind::Double).wrap(),
+                            usize::MAX,
                         ));
                 }
-                _ => {}
             }
         }))
     }
 
     fn priority(&self) -> usize {
-        0
+        1000
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum JumpDirection {
-    Left,
-    Up,
-    Right,
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AirDashState {
+    pub direction: DashDirection,
+    /// Frames since the air dash started
+    pub frame: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct AirborneState(pub JumpDirection);
-
-impl State for AirborneState {
+impl State for AirDashState {
     fn on_enter(
         &mut self,
         player: PlayerSide,
     ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
-        let dir = self.0;
         Some(Box::new(move |state, info| {
-            state.player_mut(player).velocity.x = FixedPoint::from(match dir {
-                JumpDirection::Left => -60.0,
-                JumpDirection::Up => 0.0,
-                JumpDirection::Right => 60.,
-            })
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            let animation = info.character_protos[&player.character].animations["air_dash"];
+            player.animator.switch_animation(frame, animation, None);
+        }))
+    }
+
+    fn pre_tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let direction = self.direction;
+        let dash_frame = self.frame;
+        self.frame += 1;
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let stats = &info.character_protos[&player.character].stats;
+
+            if dash_frame >= stats.air_dash_frames {
+                // Keep drifting in the direction of the dash
+                let forward = direction == DashDirection::Forward;
+                let jump_direction = match (player.facing, forward) {
+                    (Facing::Right, true) | (Facing::Left, false) => JumpDirection::Right,
+                    (Facing::Right, false) | (Facing::Left, true) => JumpDirection::Left,
+                };
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(
+                        AirborneState(jump_direction, JumpKind::Normal).wrap(),
+                        usize::MAX,
+                    ));
+                return;
+            }
+
+            let speed = match direction {
+                DashDirection::Forward => stats.air_dash_speed,
+                DashDirection::Backward => -stats.air_dash_speed,
+            };
+            player.velocity.x = match player.facing {
+                Facing::Left => -speed,
+                Facing::Right => speed,
+            };
+            // Counteract gravity for the duration of the dash
+            player.velocity.y = FixedPoint::ZERO;
         }))
     }
 
@@ -255,7 +871,7 @@ impl State for AirborneState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BlockstunState(pub usize);
 
 impl State for BlockstunState {
@@ -282,7 +898,7 @@ impl State for BlockstunState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct HitstunState(pub usize);
 
 impl State for HitstunState {
@@ -324,12 +940,110 @@ impl State for HitstunState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer)]
+/// Hit reaction where the player slowly collapses, leaving them open for a follow-up. Can't be
+/// blocked out of, and is only left when the given amount of frames has passed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CrumpleState(pub usize);
+
+impl State for CrumpleState {
+    fn pre_tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        if self.0 <= 1 {
+            Some(Box::new(move |state, _| {
+                state
+                    .player_mut(player)
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(IdleState.wrap(), usize::MAX))
+            }))
+        } else {
+            self.0 -= 1;
+            None
+        }
+    }
+
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            player.velocity.x = FixedPoint::ZERO;
+            player.animator.switch_animation(
+                frame,
+                info.character_protos[&player.character].animations["hitstun"],
+                None,
+            )
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        1000
+    }
+}
+
+/// Stun after the guard gauge has been emptied. The gauge is refilled once the stun is over.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GuardCrushState(pub usize);
+
+impl State for GuardCrushState {
+    fn pre_tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        if self.0 <= 1 {
+            Some(Box::new(move |state, _| {
+                state
+                    .player_mut(player)
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(IdleState.wrap(), usize::MAX))
+            }))
+        } else {
+            self.0 -= 1;
+            None
+        }
+    }
+
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player = state.player_mut(player);
+            player.animator.switch_animation(
+                frame,
+                info.character_protos[&player.character].animations["hitstun"],
+                None,
+            )
+        }))
+    }
+
+    fn on_exit(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        Some(Box::new(move |state, _| {
+            state.player_mut(player).guard = FixedPoint::from(MAX_GUARD);
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        1000
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer, Hash, Serialize, Deserialize)]
 pub enum CharacterSpecificState {
     Guy(guy::GuyState),
+    Scripted(ScriptedState),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Facing {
     Left,
     Right,
@@ -1,22 +1,27 @@
 use macros::CharacterStateContainer;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     game::{
-        movelist::{InputMatcher, Move, Movelist, StateMatcher, HitEffect, Effect},
+        movelist::{InputMatcher, Move, Movelist, StateMatcher, HitEffect, Effect, CounterModifiers},
+        projectile::ProjectileSpawn,
+        fixed_point::Vec2,
         GameInfo, GameState, PlayerSide, Attack,
         input::Button,
         time::Frame, fixed_point::FixedPoint,
     }
 };
 
-use super::{CharacterSpecificState, CharacterState, IdleState, State};
+use super::{CharacterSpecificState, CharacterState, CrumpleState, IdleState, State};
 
-#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer)]
+#[derive(Debug, Clone, PartialEq, Eq, CharacterStateContainer, Hash, Serialize, Deserialize)]
 pub enum GuyState {
     Normal(Normal),
+    Special(Special),
+    Super(Super),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Normal {
     NeutralPunch,
 }
@@ -35,14 +40,24 @@ impl State for Normal {
                     info.character_protos[&player.character].animations["punch"],
                     Some(IdleState.wrap()),
                 );
-                player.current_attack = Some(Attack {
-                    has_hit_player: false,
-                    hit_effect: HitEffect {
-                        unblockable: false,
-                        effects_on_hit: vec![Effect::Hitstun(14), Effect::Knockback(FixedPoint::from(30.0)), Effect::Damage(2.into())],
-                        effects_on_block: vec![Effect::Blockstun(14), Effect::Knockback(FixedPoint::from(30.0))],
+                player.current_attack = Some(Attack::new(HitEffect {
+                    unblockable: false,
+                    effects_on_hit: vec![Effect::Hitstun(14), Effect::Knockback(FixedPoint::from(30.0)), Effect::Damage(2.into())],
+                    effects_on_block: vec![Effect::Blockstun(14), Effect::Knockback(FixedPoint::from(30.0)), Effect::GuardDamage(8.into())],
+                    self_effects_on_hit: vec![],
+                    self_effects_on_block: vec![],
+                    meter_gain_on_hit: FixedPoint::from(5),
+                    meter_gain_on_block: FixedPoint::from(2),
+                    counter_hit: CounterModifiers {
+                        damage: FixedPoint::from(1.25),
+                        hitstun: FixedPoint::from(1.5),
                     },
-                })
+                    punish_counter: CounterModifiers {
+                        damage: FixedPoint::from(1.5),
+                        hitstun: FixedPoint::from(2.0),
+                    },
+                    starter_scaling: FixedPoint::from(0.8),
+                }));
             })),
         }
     }
@@ -65,13 +80,167 @@ impl State for Normal {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Special {
+    Fireball,
+}
+
+impl State for Special {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        match self {
+            Special::Fireball => Some(Box::new(move |state, info| {
+                let frame = state.current_frame;
+                let player = state.player_mut(player);
+                player.animator.make_sure_animation(
+                    frame,
+                    info.character_protos[&player.character].animations["fireball_throw"],
+                    Some(IdleState.wrap()),
+                );
+            })),
+        }
+    }
+
+    fn priority(&self) -> usize {
+        match self {
+            Special::Fireball => 20,
+        }
+    }
+}
+
+pub(super) fn fireball() -> ProjectileSpawn {
+    ProjectileSpawn {
+        animation: "fireball",
+        offset: Vec2::new(FixedPoint::from(20), -FixedPoint::from(48)),
+        velocity: Vec2::new(FixedPoint::from(120), FixedPoint::ZERO),
+        hit_effect: HitEffect {
+            unblockable: false,
+            effects_on_hit: vec![Effect::Hitstun(20), Effect::Knockback(FixedPoint::from(40.0)), Effect::Damage(5.into())],
+            effects_on_block: vec![Effect::Blockstun(16), Effect::Knockback(FixedPoint::from(30.0)), Effect::ChipDamage(1.into()), Effect::GuardDamage(10.into())],
+            self_effects_on_hit: vec![],
+            self_effects_on_block: vec![],
+            meter_gain_on_hit: FixedPoint::from(5),
+            meter_gain_on_block: FixedPoint::from(3),
+            counter_hit: CounterModifiers::NONE,
+            punish_counter: CounterModifiers::NONE,
+            starter_scaling: FixedPoint::ONE,
+        },
+        hits: 1,
+        hit_interval: 0,
+        lifetime: 180,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Super {
+    BigPunch,
+}
+
+impl State for Super {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        match self {
+            Super::BigPunch => Some(Box::new(move |state, info| {
+                let frame = state.current_frame;
+                let player = state.player_mut(player);
+                player.animator.make_sure_animation(
+                    frame,
+                    info.character_protos[&player.character].animations["big_punch"],
+                    Some(IdleState.wrap()),
+                );
+                let hit_effect = |damage: usize| HitEffect {
+                    unblockable: false,
+                    // Crumpling stops the opponent in place, so the second hit connects too
+                    effects_on_hit: vec![
+                        Effect::StateChange(CrumpleState(60).wrap()),
+                        Effect::Damage(damage.into()),
+                    ],
+                    effects_on_block: vec![
+                        Effect::Blockstun(20),
+                        Effect::Knockback(FixedPoint::from(30.0)),
+                        Effect::ChipDamage(2.into()),
+                        Effect::GuardDamage(13.into()),
+                    ],
+                    self_effects_on_hit: vec![],
+                    self_effects_on_block: vec![],
+                    meter_gain_on_hit: FixedPoint::ZERO,
+                    meter_gain_on_block: FixedPoint::ZERO,
+                    counter_hit: CounterModifiers::NONE,
+                    punish_counter: CounterModifiers {
+                        damage: FixedPoint::from(1.2),
+                        hitstun: FixedPoint::ONE,
+                    },
+                    starter_scaling: FixedPoint::ONE,
+                };
+                // Hits twice, harder with the fist than with the forearm
+                player.current_attack = Some(
+                    Attack::new(hit_effect(8))
+                        .with_hitbox_group("Fist", hit_effect(12))
+                        .with_rehit(2, 5),
+                );
+            })),
+        }
+    }
+
+    fn on_exit(
+            &mut self,
+            player: PlayerSide,
+        ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        match self {
+            Super::BigPunch => Some(Box::new(move |state, _| {
+                state.player_mut(player).current_attack = None;
+            })),
+        }
+    }
+
+    fn priority(&self) -> usize {
+        match self {
+            Super::BigPunch => 30,
+        }
+    }
+}
+
 pub fn movelist() -> Movelist {
-    Movelist::new([Move {
-        name: "Punch".into(),
-        input_matcher: InputMatcher::neutral_normal(Button::Punch),
-        state_matcher: StateMatcher::idle(),
-        priority: 10,
-        new_state: Normal::NeutralPunch.wrap().wrap().wrap(),
-        stops_momentum: true,
-    }])
+    Movelist::new([
+        Move {
+            name: "Punch".into(),
+            input_matcher: InputMatcher::neutral_normal(Button::Punch),
+            state_matcher: StateMatcher::idle(),
+            priority: 10,
+            new_state: Normal::NeutralPunch.wrap().wrap().wrap(),
+            stops_momentum: true,
+            meter_cost: FixedPoint::ZERO,
+            meter_gain: FixedPoint::ZERO,
+            super_freeze: 0,
+            self_effects: vec![],
+        },
+        Move {
+            name: "Fireball".into(),
+            input_matcher: InputMatcher::neutral_normal(Button::Kick),
+            state_matcher: StateMatcher::idle(),
+            priority: 20,
+            new_state: Special::Fireball.wrap().wrap().wrap(),
+            stops_momentum: true,
+            meter_cost: FixedPoint::ZERO,
+            meter_gain: FixedPoint::from(3),
+            super_freeze: 0,
+            self_effects: vec![],
+        },
+        Move {
+            name: "Big Punch".into(),
+            input_matcher: InputMatcher::button_combo(vec![Button::Punch, Button::Kick]),
+            state_matcher: StateMatcher::idle(),
+            priority: 30,
+            new_state: Super::BigPunch.wrap().wrap().wrap(),
+            stops_momentum: true,
+            meter_cost: FixedPoint::from(crate::game::MAX_METER),
+            meter_gain: FixedPoint::ZERO,
+            super_freeze: 45,
+            self_effects: vec![],
+        },
+    ])
 }
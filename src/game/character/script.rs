@@ -0,0 +1,511 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    animation::AnimationId,
+    data_error::{DataError, DataErrorKind},
+    fixed_point::FixedPoint,
+    input::Button,
+    movelist::{CounterModifiers, Effect, HitEffect, InputMatcher, Move, Movelist, StateMatcher},
+    time::Frame,
+    Attack, GameInfo, GameState, PlayerSide,
+};
+
+use super::{
+    CharacterSpecificState, CharacterState, Facing, IdleState, State, StateTransitionRequest,
+};
+
+/// A character-specific state described in a script file rather than in Rust. The priority and
+/// cancel windows of the state are built into the moves when the script is loaded.
+pub struct StateDefinition {
+    pub animation: String,
+    /// Animations played after `animation`, in order. The last one loops according to its loop
+    /// mode for as long as the state lasts.
+    pub then: Vec<String>,
+    /// Frames until the state returns to idle. `None` returns to idle when the last animation
+    /// ends.
+    pub duration: Option<usize>,
+    /// Returns to idle once the current animation is this far along, from 0 to 1, for states
+    /// that can act again before their animation is over
+    pub end_at_progress: Option<FixedPoint>,
+    pub velocity_changes: Vec<VelocityChange>,
+    pub attack: Option<HitEffect>,
+    pub on_enter: Vec<Effect>,
+    pub on_exit: Vec<Effect>,
+}
+
+/// Sets the velocity of the player on a frame of a scripted state
+pub struct VelocityChange {
+    pub frame: usize,
+    /// As if facing right. Left unchanged if `None`.
+    pub x: Option<FixedPoint>,
+    pub y: Option<FixedPoint>,
+}
+
+/// The states and moves of a character defined in a script file
+pub struct CharacterScript {
+    pub states: Vec<StateDefinition>,
+    pub movelist: Movelist,
+}
+
+impl CharacterScript {
+    /// Loads a script, checking that its states only play animations from `animations`
+    pub fn load(
+        path: impl AsRef<Path>,
+        animations: &HashMap<&'static str, AnimationId>,
+    ) -> Result<Self, DataError> {
+        let path = path.as_ref();
+        let script: interface::CharacterScript = DataError::read_json(path)?;
+
+        for state in &script.states {
+            for animation in std::iter::once(&state.animation).chain(&state.then) {
+                if !animations.contains_key(animation.as_str()) {
+                    return Err(DataError::new(
+                        path,
+                        DataErrorKind::UnknownAnimation {
+                            state: state.name.clone(),
+                            animation: animation.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        let moves = script
+            .moves
+            .iter()
+            .map(|mov| {
+                let id = script
+                    .states
+                    .iter()
+                    .position(|state| state.name == mov.state)
+                    .ok_or_else(|| {
+                        DataError::new(
+                            path,
+                            DataErrorKind::UnknownState {
+                                mov: mov.name.clone(),
+                                state: mov.state.clone(),
+                            },
+                        )
+                    })?;
+                let priority = script.states[id].priority;
+
+                // Every window of every state that lists this move as a cancel
+                let windows: Vec<(usize, usize, usize)> = script
+                    .states
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(from_id, state)| {
+                        state
+                            .cancels
+                            .iter()
+                            .filter(|cancel| cancel.into.contains(&mov.name))
+                            .map(move |cancel| (from_id, cancel.window[0], cancel.window[1]))
+                    })
+                    .collect();
+                for &(from_id, _, _) in &windows {
+                    if priority <= script.states[from_id].priority {
+                        return Err(DataError::new(
+                            path,
+                            DataErrorKind::CancelPriority {
+                                from: script.states[from_id].name.clone(),
+                                into: mov.state.clone(),
+                            },
+                        ));
+                    }
+                }
+
+                let from_idle = mov.from.iter().any(|s| s == "idle");
+                let from_airborne = mov.from.iter().any(|s| s == "airborne");
+
+                Ok(Move {
+                    name: mov.name.clone(),
+                    input_matcher: match &mov.input {
+                        interface::Input::Button(button) => {
+                            InputMatcher::neutral_normal(button.into())
+                        }
+                        interface::Input::Combo(buttons) => InputMatcher::button_combo(
+                            buttons.iter().map(Button::from).collect(),
+                        ),
+                    },
+                    state_matcher: StateMatcher::new(move |state| match state {
+                        CharacterState::Idle(_) => from_idle,
+                        CharacterState::Airborne(_) => from_airborne,
+                        CharacterState::CharacterSpecific(CharacterSpecificState::Scripted(
+                            state,
+                        )) => windows.iter().any(|&(from_id, start, end)| {
+                            state.id == from_id && (start..=end).contains(&state.frame)
+                        }),
+                        _ => false,
+                    }),
+                    priority: mov.priority,
+                    new_state: ScriptedState {
+                        id,
+                        frame: 0,
+                        priority,
+                    }
+                    .wrap()
+                    .wrap(),
+                    stops_momentum: mov.stops_momentum,
+                    meter_cost: mov.meter_cost.into(),
+                    meter_gain: mov.meter_gain.into(),
+                    super_freeze: mov.super_freeze,
+                    self_effects: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let states = script
+            .states
+            .into_iter()
+            .map(|state| StateDefinition {
+                animation: state.animation,
+                then: state.then,
+                duration: state.duration,
+                end_at_progress: state.end_at_progress.map(FixedPoint::from),
+                velocity_changes: state
+                    .velocity
+                    .into_iter()
+                    .map(|change| VelocityChange {
+                        frame: change.frame,
+                        x: change.x.map(FixedPoint::from),
+                        y: change.y.map(FixedPoint::from),
+                    })
+                    .collect(),
+                attack: state.attack.map(HitEffect::from),
+                on_enter: state.on_enter.into_iter().map(Effect::from).collect(),
+                on_exit: state.on_exit.into_iter().map(Effect::from).collect(),
+            })
+            .collect();
+
+        Ok(Self {
+            states,
+            movelist: Movelist::new(moves),
+        })
+    }
+}
+
+/// A state running a `StateDefinition` of the player's character
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScriptedState {
+    /// Index into `CharacterProto::scripted_states`
+    pub id: usize,
+    /// Frames since the state was entered
+    pub frame: usize,
+    /// Copied from the definition, since `priority` has no access to the game info
+    pub priority: usize,
+}
+
+impl State for ScriptedState {
+    fn on_enter(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let id = self.id;
+        Some(Box::new(move |state, info| {
+            let frame = state.current_frame;
+            let player_data = state.player_mut(player);
+            let proto = &info.character_protos[&player_data.character];
+            let def = &proto.scripted_states[id];
+
+            let state_after = def.duration.is_none().then(|| IdleState.wrap());
+            player_data.animator.switch_animation(
+                frame,
+                proto.animations[def.animation.as_str()],
+                state_after,
+            );
+            for animation in &def.then {
+                player_data
+                    .animator
+                    .queue_animation(proto.animations[animation.as_str()]);
+            }
+            player_data.current_attack = def.attack.clone().map(Attack::new);
+
+            for effect in &def.on_enter {
+                state.apply_effect(player, effect, info);
+            }
+        }))
+    }
+
+    fn on_exit(
+        &mut self,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let id = self.id;
+        Some(Box::new(move |state, info| {
+            let player_data = state.player_mut(player);
+            player_data.current_attack = None;
+            let def = &info.character_protos[&player_data.character].scripted_states[id];
+
+            for effect in &def.on_exit {
+                state.apply_effect(player, effect, info);
+            }
+        }))
+    }
+
+    fn tick(
+        &mut self,
+        frame: Frame,
+        player: PlayerSide,
+    ) -> Option<Box<dyn FnOnce(&mut GameState, &GameInfo)>> {
+        let id = self.id;
+        let state_frame = self.frame;
+        self.frame += 1;
+        Some(Box::new(move |state, info| {
+            let player = state.player_mut(player);
+            let def = &info.character_protos[&player.character].scripted_states[id];
+
+            for change in def
+                .velocity_changes
+                .iter()
+                .filter(|change| change.frame == state_frame)
+            {
+                if let Some(x) = change.x {
+                    player.velocity.x = match player.facing {
+                        Facing::Left => -x,
+                        Facing::Right => x,
+                    };
+                }
+                if let Some(y) = change.y {
+                    player.velocity.y = y;
+                }
+            }
+
+            let ended = def.duration.is_some_and(|duration| state_frame + 1 >= duration)
+                || def.end_at_progress.is_some_and(|end| {
+                    player.animator.progress(&info.animations, frame) >= end
+                });
+            if ended {
+                player
+                    .state_transition_requests
+                    .add(StateTransitionRequest::new(IdleState.wrap(), usize::MAX));
+            }
+        }))
+    }
+
+    fn priority(&self) -> usize {
+        self.priority
+    }
+}
+
+impl From<&interface::Button> for Button {
+    fn from(value: &interface::Button) -> Self {
+        match value {
+            interface::Button::Punch => Button::Punch,
+            interface::Button::Kick => Button::Kick,
+        }
+    }
+}
+
+impl From<interface::Effect> for Effect {
+    fn from(value: interface::Effect) -> Self {
+        match value {
+            interface::Effect::Damage(amt) => Effect::Damage(amt.into()),
+            interface::Effect::Hitstun(frames) => Effect::Hitstun(frames),
+            interface::Effect::Blockstun(frames) => Effect::Blockstun(frames),
+            interface::Effect::Knockback(amt) => Effect::Knockback(amt.into()),
+            interface::Effect::PullIn(amt) => Effect::PullIn(amt.into()),
+            interface::Effect::WallBounce(amt) => Effect::WallBounce(amt.into()),
+            interface::Effect::GroundBounce(amt) => Effect::GroundBounce(amt.into()),
+            interface::Effect::ChipDamage(amt) => Effect::ChipDamage(amt.into()),
+            interface::Effect::GuardDamage(amt) => Effect::GuardDamage(amt.into()),
+        }
+    }
+}
+
+impl From<interface::CounterModifiers> for CounterModifiers {
+    fn from(value: interface::CounterModifiers) -> Self {
+        Self {
+            damage: value.damage.into(),
+            hitstun: value.hitstun.into(),
+        }
+    }
+}
+
+impl From<interface::HitEffect> for HitEffect {
+    fn from(value: interface::HitEffect) -> Self {
+        Self {
+            unblockable: value.unblockable,
+            effects_on_hit: value.effects_on_hit.into_iter().map(Effect::from).collect(),
+            effects_on_block: value.effects_on_block.into_iter().map(Effect::from).collect(),
+            self_effects_on_hit: value
+                .self_effects_on_hit
+                .into_iter()
+                .map(Effect::from)
+                .collect(),
+            self_effects_on_block: value
+                .self_effects_on_block
+                .into_iter()
+                .map(Effect::from)
+                .collect(),
+            meter_gain_on_hit: value.meter_gain_on_hit.into(),
+            meter_gain_on_block: value.meter_gain_on_block.into(),
+            counter_hit: value
+                .counter_hit
+                .map_or(CounterModifiers::NONE, CounterModifiers::from),
+            punish_counter: value
+                .punish_counter
+                .map_or(CounterModifiers::NONE, CounterModifiers::from),
+            starter_scaling: value.starter_scaling.map_or(FixedPoint::ONE, FixedPoint::from),
+        }
+    }
+}
+
+mod interface {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CharacterScript {
+        pub states: Vec<State>,
+        pub moves: Vec<Move>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct State {
+        pub name: String,
+        pub animation: String,
+        #[serde(default)]
+        pub then: Vec<String>,
+        #[serde(default)]
+        pub duration: Option<usize>,
+        #[serde(default)]
+        pub end_at_progress: Option<f32>,
+        pub priority: usize,
+        #[serde(default)]
+        pub velocity: Vec<VelocityChange>,
+        #[serde(default)]
+        pub attack: Option<HitEffect>,
+        #[serde(default)]
+        pub on_enter: Vec<Effect>,
+        #[serde(default)]
+        pub on_exit: Vec<Effect>,
+        #[serde(default)]
+        pub cancels: Vec<CancelWindow>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct VelocityChange {
+        pub frame: usize,
+        #[serde(default)]
+        pub x: Option<f32>,
+        #[serde(default)]
+        pub y: Option<f32>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CancelWindow {
+        pub window: [usize; 2],
+        pub into: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Move {
+        pub name: String,
+        pub input: Input,
+        /// Name of the scripted state the move goes into
+        pub state: String,
+        /// Common states the move can be performed from, on top of any cancel windows
+        #[serde(default)]
+        pub from: Vec<String>,
+        pub priority: usize,
+        #[serde(default)]
+        pub stops_momentum: bool,
+        #[serde(default)]
+        pub meter_cost: f32,
+        #[serde(default)]
+        pub meter_gain: f32,
+        #[serde(default)]
+        pub super_freeze: usize,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Input {
+        Button(Button),
+        Combo(Vec<Button>),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Button {
+        Punch,
+        Kick,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Effect {
+        Damage(f32),
+        Hitstun(usize),
+        Blockstun(usize),
+        Knockback(f32),
+        PullIn(f32),
+        WallBounce(f32),
+        GroundBounce(f32),
+        ChipDamage(f32),
+        GuardDamage(f32),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CounterModifiers {
+        pub damage: f32,
+        pub hitstun: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct HitEffect {
+        #[serde(default)]
+        pub unblockable: bool,
+        #[serde(default)]
+        pub effects_on_hit: Vec<Effect>,
+        #[serde(default)]
+        pub effects_on_block: Vec<Effect>,
+        #[serde(default)]
+        pub self_effects_on_hit: Vec<Effect>,
+        #[serde(default)]
+        pub self_effects_on_block: Vec<Effect>,
+        #[serde(default)]
+        pub meter_gain_on_hit: f32,
+        #[serde(default)]
+        pub meter_gain_on_block: f32,
+        #[serde(default)]
+        pub counter_hit: Option<CounterModifiers>,
+        #[serde(default)]
+        pub punish_counter: Option<CounterModifiers>,
+        #[serde(default)]
+        pub starter_scaling: Option<f32>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::animation::{AnimationStore, LoopMode};
+
+    use super::*;
+
+    #[test]
+    fn states_with_unknown_animations_are_rejected() {
+        let path = std::env::temp_dir().join("fg-sdl2-unknown-animation-script.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "states": [{ "name": "jab", "animation": "punch", "then": ["kick"], "priority": 10 }],
+                "moves": []
+            }"#,
+        )
+        .unwrap();
+        let mut store = AnimationStore::new();
+        let animations = HashMap::from([(
+            "punch",
+            store.add_test_animation(vec![(1, vec![])], LoopMode::Hold),
+        )]);
+
+        let error = CharacterScript::load(&path, &animations).err().unwrap();
+        assert!(matches!(
+            &error.kind,
+            DataErrorKind::UnknownAnimation { state, animation } if state == "jab" && animation == "kick"
+        ));
+        assert!(error
+            .to_string()
+            .ends_with("state jab: animation: unknown animation kick"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
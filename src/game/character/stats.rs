@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use crate::game::{
+    data_error::{DataError, DataErrorKind},
+    fixed_point::FixedPoint,
+};
+
+pub struct CharacterStats {
+    pub health: FixedPoint,
+    pub walk_speed: FixedPoint,
+    pub back_walk_speed: FixedPoint,
+    pub jump_velocity: FixedPoint,
+    /// Horizontal speed of forward and backward jumps
+    pub jump_speed: FixedPoint,
+    /// Frames spent on the ground before actually leaving it when jumping
+    pub jump_squat_frames: usize,
+    pub super_jump_velocity: FixedPoint,
+    /// Extra jumps that can be done while airborne
+    pub air_jumps: usize,
+    pub air_dash_speed: FixedPoint,
+    /// Set to 0 to disable air dashing
+    pub air_dash_frames: usize,
+    pub gravity: FixedPoint,
+    /// Horizontal deceleration while on the ground
+    pub friction: FixedPoint,
+    /// Knockback is divided by this
+    pub weight: FixedPoint,
+    pub dash_speed: FixedPoint,
+    pub dash_frames: usize,
+    /// Speed of the run that follows a dash while forward is held
+    pub run_speed: FixedPoint,
+    pub backdash_speed: FixedPoint,
+    pub backdash_frames: usize,
+    /// Frames at the start of a backdash during which the player can't be hit
+    pub backdash_invincibility: usize,
+}
+
+impl CharacterStats {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        let stats: interface::CharacterStats = DataError::read_json(path.as_ref())?;
+        validate(&stats).map_err(|kind| DataError::new(path.as_ref(), kind))?;
+
+        Ok(Self {
+            health: stats.health.into(),
+            walk_speed: stats.walk_speed.into(),
+            back_walk_speed: stats.back_walk_speed.into(),
+            jump_velocity: stats.jump_velocity.into(),
+            jump_speed: stats.jump_speed.into(),
+            jump_squat_frames: stats.jump_squat_frames,
+            super_jump_velocity: stats.super_jump_velocity.into(),
+            air_jumps: stats.air_jumps,
+            air_dash_speed: stats.air_dash_speed.into(),
+            air_dash_frames: stats.air_dash_frames,
+            gravity: stats.gravity.into(),
+            friction: stats.friction.into(),
+            weight: stats.weight.into(),
+            dash_speed: stats.dash_speed.into(),
+            dash_frames: stats.dash_frames,
+            run_speed: stats.run_speed.into(),
+            backdash_speed: stats.backdash_speed.into(),
+            backdash_frames: stats.backdash_frames,
+            backdash_invincibility: stats.backdash_invincibility,
+        })
+    }
+}
+
+/// Rejects stats the game can't work with
+fn validate(stats: &interface::CharacterStats) -> Result<(), DataErrorKind> {
+    // Knockback is divided by the weight, and players without gravity or a jump velocity
+    // would never come back down or leave the ground
+    let positive = [
+        ("health", stats.health),
+        ("weight", stats.weight),
+        ("gravity", stats.gravity),
+        ("jump_velocity", stats.jump_velocity),
+        ("super_jump_velocity", stats.super_jump_velocity),
+    ];
+    let non_negative = [
+        ("walk_speed", stats.walk_speed),
+        ("back_walk_speed", stats.back_walk_speed),
+        ("jump_speed", stats.jump_speed),
+        ("air_dash_speed", stats.air_dash_speed),
+        ("friction", stats.friction),
+        ("dash_speed", stats.dash_speed),
+        ("run_speed", stats.run_speed),
+        ("backdash_speed", stats.backdash_speed),
+    ];
+
+    let invalid = |stat, value, expected| DataErrorKind::InvalidStat {
+        stat,
+        value,
+        expected,
+    };
+    for (stat, value) in positive {
+        if value <= 0.0 {
+            return Err(invalid(stat, value, "more than 0"));
+        }
+    }
+    for (stat, value) in non_negative {
+        if value < 0.0 {
+            return Err(invalid(stat, value, "at least 0"));
+        }
+    }
+    Ok(())
+}
+
+mod interface {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CharacterStats {
+        pub health: f32,
+        pub walk_speed: f32,
+        pub back_walk_speed: f32,
+        pub jump_velocity: f32,
+        pub jump_speed: f32,
+        pub jump_squat_frames: usize,
+        pub super_jump_velocity: f32,
+        pub air_jumps: usize,
+        pub air_dash_speed: f32,
+        pub air_dash_frames: usize,
+        pub gravity: f32,
+        pub friction: f32,
+        pub weight: f32,
+        pub dash_speed: f32,
+        pub dash_frames: usize,
+        pub run_speed: f32,
+        pub backdash_speed: f32,
+        pub backdash_frames: usize,
+        pub backdash_invincibility: usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_shipped_stats() {
+        let guy = CharacterStats::load("assets/characters/guy.json").unwrap();
+        assert_eq!(guy.health, FixedPoint::from(100usize));
+        assert_eq!(guy.walk_speed, FixedPoint::from(60usize));
+        assert_eq!(guy.jump_squat_frames, 4);
+        assert_eq!(guy.weight, FixedPoint::ONE);
+
+        let dude = CharacterStats::load("assets/characters/dude.json").unwrap();
+        assert_eq!(dude.air_jumps, 0);
+        assert_eq!(dude.air_dash_frames, 0);
+        assert!(dude.weight > guy.weight);
+    }
+
+    #[test]
+    fn missing_fields_are_reported_with_the_path() {
+        let path = std::env::temp_dir().join("fg-sdl2-missing-field-stats.json");
+        std::fs::write(&path, r#"{ "health": 100.0 }"#).unwrap();
+
+        let error = CharacterStats::load(&path).err().unwrap();
+        assert!(matches!(error.kind, DataErrorKind::Json(_)));
+        assert_eq!(error.path, path);
+        assert!(error.to_string().contains("walk_speed"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unusable_stats_are_rejected() {
+        let path = std::env::temp_dir().join("fg-sdl2-weightless-stats.json");
+        let mut stats: serde_json::Value =
+            serde_json::from_slice(&std::fs::read("assets/characters/guy.json").unwrap()).unwrap();
+        stats["weight"] = 0.0.into();
+        std::fs::write(&path, serde_json::to_vec(&stats).unwrap()).unwrap();
+
+        let error = CharacterStats::load(&path).err().unwrap();
+        assert!(matches!(
+            error.kind,
+            DataErrorKind::InvalidStat { stat: "weight", .. }
+        ));
+        assert!(error
+            .to_string()
+            .ends_with("weight: found 0, expected more than 0"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_reported_with_the_path() {
+        let error = CharacterStats::load("assets/characters/nobody.json")
+            .err()
+            .unwrap();
+        assert!(matches!(error.kind, DataErrorKind::Io(_)));
+        assert!(error
+            .to_string()
+            .starts_with("assets/characters/nobody.json: "));
+    }
+}
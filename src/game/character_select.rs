@@ -0,0 +1,244 @@
+use sdl2::{event::Event, pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+use crate::{
+    app_context::AppContext,
+    state::{State, StateTransition},
+};
+
+use super::{
+    animation::Animation,
+    character::{Character, CharacterChoice},
+    input::{Action, BoxedInput},
+    GameInfo, GameRunner, GameState, PlayerSide, PlayerType,
+};
+
+const PORTRAIT_SCALE: u32 = 4;
+const PREVIEW_SCALE: u32 = 8;
+const SLOT_SPACING: u32 = 32;
+
+/// Lets both players pick a character before the match starts
+pub struct CharacterSelect {
+    /// Handed over to the game runner once both players have picked
+    info: Option<GameInfo>,
+    player_side: Option<PlayerSide>,
+    cursors: [usize; 2],
+    palettes: [usize; 2],
+    picks: [Option<CharacterChoice>; 2],
+    last_inputs: [BoxedInput; 2],
+}
+
+impl CharacterSelect {
+    pub fn new(player_side: Option<PlayerSide>, info: GameInfo) -> Self {
+        Self {
+            info: Some(info),
+            player_side,
+            cursors: [0, Character::ALL.len().min(2) - 1],
+            palettes: [0, 0],
+            picks: [None, None],
+            last_inputs: [BoxedInput::new(), BoxedInput::new()],
+        }
+    }
+
+    fn info(&self) -> &GameInfo {
+        self.info.as_ref().unwrap()
+    }
+
+    /// The characters as they would be picked right now, with the second player's palette
+    /// changed if it would be identical to the first player's
+    fn choices(&self) -> [CharacterChoice; 2] {
+        let [player_1, player_2] = [0, 1].map(|i| CharacterChoice {
+            character: Character::ALL[self.cursors[i]],
+            palette: self.palettes[i],
+        });
+        let palette_count = self.info().character_protos[&player_2.character]
+            .palettes
+            .len();
+        [player_1, player_2.distinct_from(player_1, palette_count)]
+    }
+
+    fn current_inputs(&self) -> [BoxedInput; 2] {
+        let info = self.info();
+        [&info.player_1, &info.player_2].map(|player| match player {
+            PlayerType::Local { input, .. } => *input,
+            PlayerType::Remote => BoxedInput::new(),
+        })
+    }
+}
+
+impl State for CharacterSelect {
+    fn on_enter(&mut self, _ctx: &mut AppContext) {}
+
+    fn on_suspend(&mut self, _ctx: &mut AppContext) {}
+
+    fn on_resume(&mut self, _ctx: &mut AppContext) {}
+
+    fn on_exit(&mut self, _ctx: &mut AppContext) {}
+
+    fn event(&mut self, event: Event, _ctx: &mut AppContext) {
+        let info = self.info.as_mut().unwrap();
+        match event {
+            Event::KeyDown {
+                scancode: Some(scancode),
+                ..
+            } => info.input(scancode, true),
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => info.input(scancode, false),
+            Event::ControllerButtonDown { button, .. } => info.input(button, true),
+            Event::ControllerButtonUp { button, .. } => info.input(button, false),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, _ctx: &mut AppContext) -> StateTransition {
+        let inputs = self.current_inputs();
+
+        for (i, input) in inputs.into_iter().enumerate() {
+            let last = self.last_inputs[i];
+            let just_pressed = |action| input.is_pressed(action) && last.is_released(action);
+
+            if self.picks[i].is_none() {
+                let count = Character::ALL.len();
+                if just_pressed(Action::MoveLeft) {
+                    self.cursors[i] = (self.cursors[i] + count - 1) % count;
+                    self.palettes[i] = 0;
+                }
+                if just_pressed(Action::MoveRight) {
+                    self.cursors[i] = (self.cursors[i] + 1) % count;
+                    self.palettes[i] = 0;
+                }
+
+                let character = Character::ALL[self.cursors[i]];
+                let palette_count = self.info().character_protos[&character].palettes.len();
+                if just_pressed(Action::MoveUp) {
+                    self.palettes[i] = (self.palettes[i] + palette_count - 1) % palette_count;
+                }
+                if just_pressed(Action::MoveDown) {
+                    self.palettes[i] = (self.palettes[i] + 1) % palette_count;
+                }
+
+                if just_pressed(Action::Punch) {
+                    self.picks[i] = Some(CharacterChoice {
+                        character,
+                        palette: self.palettes[i],
+                    });
+                }
+            } else if just_pressed(Action::Kick) {
+                self.picks[i] = None;
+            }
+        }
+        self.last_inputs = inputs;
+
+        if let [Some(player_1), Some(player_2)] = self.picks {
+            let info = self.info.take().unwrap();
+            let state = GameState::new(&info, player_1, player_2);
+            StateTransition::PopPush(1, Box::new(GameRunner::new(self.player_side, info, state)))
+        } else {
+            StateTransition::None
+        }
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, _ctx: &mut AppContext) {
+        canvas.set_draw_color(Color::RGB(20, 20, 30));
+        canvas.clear();
+
+        let Some(info) = &self.info else {
+            return;
+        };
+
+        let (width, height) = canvas.window().size();
+
+        let portraits: Vec<_> = Character::ALL
+            .iter()
+            .map(|character| {
+                &info.animations[info.character_protos[character].animations["idle"]]
+            })
+            .collect();
+        let total_width: u32 = portraits
+            .iter()
+            .map(|anim| anim.cell_width as u32 * PORTRAIT_SCALE + SLOT_SPACING)
+            .sum::<u32>()
+            - SLOT_SPACING;
+
+        let mut x = (width as i32 - total_width as i32) / 2;
+        for (slot, anim) in portraits.into_iter().enumerate() {
+            let w = anim.cell_width as u32 * PORTRAIT_SCALE;
+            let h = anim.cell_height as u32 * PORTRAIT_SCALE;
+            let y = (height as i32 - h as i32) / 2;
+
+            draw_first_frame(canvas, info, anim, 0, (x, y), PORTRAIT_SCALE, false);
+
+            for (i, color) in [Color::RED, Color::BLUE].into_iter().enumerate() {
+                if self.cursors[i] != slot {
+                    continue;
+                }
+                let bar_y = if i == 0 { y - 16 } else { y + h as i32 + 8 };
+                canvas.set_draw_color(color);
+                if self.picks[i].is_some() {
+                    canvas.fill_rect(Rect::new(x, bar_y, w, 8)).unwrap();
+                } else {
+                    canvas.draw_rect(Rect::new(x, bar_y, w, 8)).unwrap();
+                }
+            }
+
+            x += (w + SLOT_SPACING) as i32;
+        }
+
+        // Large previews of each player's character in the palette they will be drawn with
+        for (i, choice) in self.choices().into_iter().enumerate() {
+            let idle = info.character_protos[&choice.character].animations["idle"];
+            let anim = &info.animations[idle];
+            let w = anim.cell_width as u32 * PREVIEW_SCALE;
+            let h = anim.cell_height as u32 * PREVIEW_SCALE;
+            let x = if i == 0 {
+                SLOT_SPACING as i32
+            } else {
+                width as i32 - w as i32 - SLOT_SPACING as i32
+            };
+            let y = height as i32 - h as i32 - SLOT_SPACING as i32;
+
+            draw_first_frame(canvas, info, anim, choice.palette, (x, y), PREVIEW_SCALE, i == 1);
+        }
+    }
+}
+
+/// Draws the first frame of the animation as if its whole cell was drawn at the given position
+fn draw_first_frame(
+    canvas: &mut Canvas<Window>,
+    info: &GameInfo,
+    anim: &Animation,
+    palette: usize,
+    (x, y): (i32, i32),
+    scale: u32,
+    flip: bool,
+) {
+    let sprite = &anim.frame_data[0].sprite;
+    if sprite.is_empty() {
+        return;
+    }
+
+    let offset_x = if flip {
+        anim.cell_width as u32 - sprite.offset_x - sprite.width
+    } else {
+        sprite.offset_x
+    };
+    let src = Rect::new(sprite.x as _, sprite.y as _, sprite.width, sprite.height);
+    let dst = Rect::new(
+        x + (offset_x * scale) as i32,
+        y + (sprite.offset_y * scale) as i32,
+        sprite.width * scale,
+        sprite.height * scale,
+    );
+    canvas
+        .copy_ex(
+            info.animations.texture(anim, sprite, palette),
+            src,
+            dst,
+            0.0,
+            None,
+            flip,
+            false,
+        )
+        .unwrap();
+}
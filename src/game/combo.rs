@@ -0,0 +1,150 @@
+use super::{fixed_point::FixedPoint, movelist::Effect, time::Frame};
+
+/// Rules for how much damage is scaled down the longer a combo goes on
+pub struct ComboScaling {
+    /// Subtracted from the scaling for every hit after the second
+    pub decay_per_hit: FixedPoint,
+    /// Scaling never goes below this
+    pub minimum: FixedPoint,
+}
+
+/// Consecutive hits taken by a player without becoming actionable in between
+#[derive(Debug, Clone, Hash)]
+pub struct Combo {
+    pub hits: usize,
+    pub damage: FixedPoint,
+    /// Scaling applied to the next hit
+    pub scaling: FixedPoint,
+    /// Frame the combo ended on, `None` while it is still going
+    pub ended: Option<Frame>,
+}
+
+impl Combo {
+    pub fn new() -> Self {
+        Self {
+            hits: 0,
+            damage: FixedPoint::ZERO,
+            scaling: FixedPoint::ONE,
+            ended: Some(Frame::new()),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ended.is_none()
+    }
+
+    /// Registers a hit, starting a new combo if the last one has ended. Returns the scaling to
+    /// apply to the damage of the hit.
+    pub fn register_hit(&mut self, starter_scaling: FixedPoint, rules: &ComboScaling) -> FixedPoint {
+        if !self.is_active() {
+            *self = Self {
+                hits: 1,
+                damage: FixedPoint::ZERO,
+                scaling: starter_scaling.max(rules.minimum),
+                ended: None,
+            };
+            return FixedPoint::ONE;
+        }
+
+        let scaling = self.scaling;
+        self.hits += 1;
+        self.scaling = (self.scaling - rules.decay_per_hit).max(rules.minimum);
+        scaling
+    }
+
+    pub fn end(&mut self, frame: Frame) {
+        if self.is_active() {
+            self.ended = Some(frame);
+        }
+    }
+
+    pub fn scale(effect: &Effect, scaling: FixedPoint) -> Effect {
+        match effect {
+            Effect::Damage(amt) => Effect::Damage(*amt * scaling),
+            effect => effect.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> ComboScaling {
+        ComboScaling {
+            decay_per_hit: FixedPoint::from(0.25),
+            minimum: FixedPoint::from(0.5),
+        }
+    }
+
+    #[test]
+    fn scaling_decays_from_the_second_hit_down_to_the_minimum() {
+        let mut combo = Combo::new();
+        let scalings: Vec<_> = (0..5)
+            .map(|_| combo.register_hit(FixedPoint::ONE, &rules()))
+            .collect();
+
+        assert_eq!(scalings, [1.0, 1.0, 0.75, 0.5, 0.5].map(FixedPoint::from));
+        assert_eq!(combo.hits, 5);
+    }
+
+    #[test]
+    fn starter_scaling_applies_from_the_second_hit() {
+        let mut combo = Combo::new();
+
+        assert_eq!(
+            combo.register_hit(FixedPoint::from(0.75), &rules()),
+            FixedPoint::ONE
+        );
+        assert_eq!(
+            combo.register_hit(FixedPoint::ONE, &rules()),
+            FixedPoint::from(0.75)
+        );
+        assert_eq!(
+            combo.register_hit(FixedPoint::ONE, &rules()),
+            FixedPoint::from(0.5)
+        );
+    }
+
+    #[test]
+    fn starter_scaling_is_clamped_to_the_minimum() {
+        let mut combo = Combo::new();
+        combo.register_hit(FixedPoint::from(0.25), &rules());
+
+        assert_eq!(
+            combo.register_hit(FixedPoint::ONE, &rules()),
+            FixedPoint::from(0.5)
+        );
+    }
+
+    #[test]
+    fn a_hit_after_the_combo_ends_starts_a_new_one() {
+        let mut combo = Combo::new();
+        for _ in 0..3 {
+            combo.register_hit(FixedPoint::ONE, &rules());
+        }
+        combo.end(Frame::new());
+        assert!(!combo.is_active());
+
+        assert_eq!(
+            combo.register_hit(FixedPoint::ONE, &rules()),
+            FixedPoint::ONE
+        );
+        assert_eq!(combo.hits, 1);
+        assert!(combo.is_active());
+    }
+
+    #[test]
+    fn only_damage_is_scaled() {
+        let half = FixedPoint::from(0.5);
+
+        assert!(matches!(
+            Combo::scale(&Effect::Damage(FixedPoint::from(10usize)), half),
+            Effect::Damage(amt) if amt == FixedPoint::from(5usize)
+        ));
+        assert!(matches!(
+            Combo::scale(&Effect::ChipDamage(FixedPoint::from(10usize)), half),
+            Effect::ChipDamage(amt) if amt == FixedPoint::from(10usize)
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{fmt::Display, path::PathBuf};
+
+/// An error in a character's stats, script or palette file
+#[derive(Debug)]
+pub struct DataError {
+    pub path: PathBuf,
+    pub kind: DataErrorKind,
+}
+
+#[derive(Debug)]
+pub enum DataErrorKind {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// Mirror matches need a second palette
+    TooFewPalettes { count: usize },
+    /// A palette has a different amount of colors than the base
+    PaletteColorCount {
+        palette: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A move uses a state the script doesn't define
+    UnknownState { mov: String, state: String },
+    /// A state plays an animation the character doesn't have
+    UnknownAnimation { state: String, animation: String },
+    /// A state can be cancelled into a move whose state doesn't have a higher priority
+    CancelPriority { from: String, into: String },
+    /// A stat has a value the game can't work with, like a weight that knockback can't be
+    /// divided by
+    InvalidStat {
+        stat: &'static str,
+        value: f32,
+        expected: &'static str,
+    },
+}
+
+impl DataError {
+    pub fn new(path: impl Into<PathBuf>, kind: DataErrorKind) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+        }
+    }
+
+    /// Reads and parses a JSON file
+    pub fn read_json<T: serde::de::DeserializeOwned>(path: impl Into<PathBuf>) -> Result<T, Self> {
+        let path = path.into();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => return Err(Self::new(path, DataErrorKind::Io(e))),
+        };
+        serde_json::from_slice(&data).map_err(|e| Self::new(path, DataErrorKind::Json(e)))
+    }
+}
+
+impl Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        match &self.kind {
+            DataErrorKind::Io(e) => write!(f, "couldn't read file: {e}"),
+            DataErrorKind::Json(e) => write!(f, "invalid JSON: {e}"),
+            DataErrorKind::TooFewPalettes { count } => write!(
+                f,
+                "palettes: found {count}, characters need at least two for mirror matches"
+            ),
+            DataErrorKind::PaletteColorCount {
+                palette,
+                expected,
+                found,
+            } => write!(
+                f,
+                "palette {palette}: colors: found {found}, expected one for each of the {expected} base colors"
+            ),
+            DataErrorKind::UnknownState { mov, state } => {
+                write!(f, "move {mov}: state: unknown state {state}")
+            }
+            DataErrorKind::UnknownAnimation { state, animation } => {
+                write!(f, "state {state}: animation: unknown animation {animation}")
+            }
+            DataErrorKind::CancelPriority { from, into } => write!(
+                f,
+                "state {from}: cancels: {into} must have a higher priority than {from} to cancel into it"
+            ),
+            DataErrorKind::InvalidStat {
+                stat,
+                value,
+                expected,
+            } => write!(f, "{stat}: found {value}, expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            DataErrorKind::Io(e) => Some(e),
+            DataErrorKind::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
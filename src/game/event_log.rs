@@ -0,0 +1,241 @@
+use std::{collections::VecDeque, fmt::Display, io::Write, panic::Location, path::Path};
+
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+use super::{
+    animation::FrameEvent,
+    character::CharacterState,
+    hud,
+    movelist::{CounterKind, Effect},
+    time::Frame,
+    PlayerSide,
+};
+
+/// Events older than this many frames are dropped from the log
+const LOG_FRAMES: usize = 600;
+/// Frames shown in the debug panel
+const PANEL_FRAMES: usize = 180;
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    StateTransition {
+        player: PlayerSide,
+        from: CharacterState,
+        to: CharacterState,
+        priority: usize,
+        requester: &'static Location<'static>,
+    },
+    /// A request that didn't have a higher priority than the current state
+    TransitionRejected {
+        player: PlayerSide,
+        current: CharacterState,
+        requested: CharacterState,
+        priority: usize,
+        requester: &'static Location<'static>,
+    },
+    MovePerformed {
+        player: PlayerSide,
+        name: String,
+    },
+    Hit {
+        attacker: PlayerSide,
+        target: PlayerSide,
+        blocked: bool,
+        counter: Option<CounterKind>,
+        /// Whether the hit came from a projectile
+        projectile: bool,
+    },
+    EffectApplied {
+        player: PlayerSide,
+        effect: Effect,
+    },
+    /// An event of a frame the player's animation entered
+    FrameEvent {
+        player: PlayerSide,
+        event: FrameEvent,
+    },
+}
+
+impl GameEvent {
+    /// The player the event is about, or the attacker for hits
+    pub fn player(&self) -> PlayerSide {
+        match self {
+            GameEvent::StateTransition { player, .. }
+            | GameEvent::TransitionRejected { player, .. }
+            | GameEvent::MovePerformed { player, .. }
+            | GameEvent::EffectApplied { player, .. }
+            | GameEvent::FrameEvent { player, .. } => *player,
+            GameEvent::Hit { attacker, .. } => *attacker,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            GameEvent::StateTransition { .. } => Color::WHITE,
+            GameEvent::TransitionRejected { .. } => Color::GRAY,
+            GameEvent::MovePerformed { .. } => Color::YELLOW,
+            GameEvent::Hit { blocked: true, .. } => Color::CYAN,
+            GameEvent::Hit { .. } => Color::RED,
+            GameEvent::EffectApplied { .. } => Color::GREEN,
+            GameEvent::FrameEvent { .. } => Color::MAGENTA,
+        }
+    }
+}
+
+impl Display for GameEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameEvent::StateTransition {
+                player,
+                from,
+                to,
+                priority,
+                requester,
+            } => write!(
+                f,
+                "{player:?}: {from:?} -> {to:?} (priority {priority}, requested at {requester})"
+            ),
+            GameEvent::TransitionRejected {
+                player,
+                current,
+                requested,
+                priority,
+                requester,
+            } => write!(
+                f,
+                "{player:?}: stayed in {current:?}, rejected {requested:?} (priority {priority}, requested at {requester})"
+            ),
+            GameEvent::MovePerformed { player, name } => write!(f, "{player:?}: performed {name}"),
+            GameEvent::Hit {
+                attacker,
+                target,
+                blocked,
+                counter,
+                projectile,
+            } => {
+                write!(f, "{attacker:?}: hit {target:?}")?;
+                if *projectile {
+                    write!(f, " with a projectile")?;
+                }
+                if *blocked {
+                    write!(f, ", blocked")?;
+                }
+                if let Some(counter) = counter {
+                    write!(f, ", {counter:?}")?;
+                }
+                Ok(())
+            }
+            GameEvent::EffectApplied { player, effect } => write!(f, "{player:?}: got {effect:?}"),
+            GameEvent::FrameEvent { player, event } => write!(f, "{player:?}: animation {event:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub frame: Frame,
+    pub event: GameEvent,
+}
+
+/// Everything of note that happened in the simulation over the last few seconds
+pub struct EventLog {
+    events: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame, event: GameEvent) {
+        while self
+            .events
+            .front()
+            .is_some_and(|old| frame.since_with_freeze(old.frame) > LOG_FRAMES)
+        {
+            self.events.pop_front();
+        }
+        self.events.push_back(LoggedEvent { frame, event });
+    }
+
+    /// Drops the events of `frame` and later, which are simulated again after a rollback to it
+    pub fn rewind(&mut self, frame: Frame) {
+        while self
+            .events
+            .back()
+            .is_some_and(|last| last.frame.number() >= frame.number())
+        {
+            self.events.pop_back();
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LoggedEvent> + '_ {
+        self.events.iter()
+    }
+
+    pub fn for_player(&self, player: PlayerSide) -> impl Iterator<Item = &LoggedEvent> + '_ {
+        self.events
+            .iter()
+            .filter(move |logged| logged.event.player() == player)
+    }
+
+    /// Writes the whole log as text, one event per line
+    pub fn export(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for logged in self.iter() {
+            writeln!(file, "{:>6} {}", logged.frame.number(), logged.event)?;
+        }
+        file.flush()
+    }
+
+    /// Draws a timeline of the last few seconds, with a row of colored marks per player
+    pub fn render(&self, current_frame: Frame, canvas: &mut Canvas<Window>) {
+        let (width, height) = canvas.window().size();
+        let mark_width = (width / PANEL_FRAMES as u32).max(1);
+        let row_height = 24;
+        let panel_height = row_height * 2 + 24;
+        let panel_y = height as i32 - panel_height as i32;
+
+        canvas.set_clip_rect(None);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        canvas
+            .fill_rect(Rect::new(0, panel_y, width, panel_height))
+            .unwrap();
+
+        hud::draw_number(
+            canvas,
+            4,
+            panel_y + 4,
+            12,
+            current_frame.number(),
+            Color::WHITE,
+        );
+
+        for (row, player) in [PlayerSide::Player1, PlayerSide::Player2]
+            .into_iter()
+            .enumerate()
+        {
+            // Events on the same frame are stacked to show how many there were
+            let mut stacks = [0; PANEL_FRAMES];
+            for logged in self.for_player(player) {
+                let age = current_frame.since_with_freeze(logged.frame);
+                if age >= PANEL_FRAMES {
+                    continue;
+                }
+                let stack = &mut stacks[age];
+                if *stack >= 5 {
+                    continue;
+                }
+
+                let x = width as i32 - (age as i32 + 1) * mark_width as i32;
+                let y = panel_y + 20 + row as i32 * row_height as i32 + *stack * 4;
+                *stack += 1;
+
+                canvas.set_draw_color(logged.event.color());
+                canvas.fill_rect(Rect::new(x, y, mark_width, 3)).unwrap();
+            }
+        }
+    }
+}
@@ -4,9 +4,11 @@ use std::{
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
+use serde::{Deserialize, Serialize};
+
 type FixedPointType = i32;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default, Serialize, Deserialize)]
 pub struct FixedPoint(FixedPointType);
 
 impl Display for FixedPoint {
@@ -28,6 +30,7 @@ fn change_scaling_factor(value: FixedPointType, from: i32, to: i32) -> FixedPoin
 
 impl FixedPoint {
     pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << Self::DECIMAL_PLACES);
 
     const DECIMAL_PLACES: usize = 8;
 
@@ -212,7 +215,7 @@ impl From<FixedPoint> for f64 {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: FixedPoint,
     pub y: FixedPoint,
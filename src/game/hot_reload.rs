@@ -0,0 +1,116 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sdl2::{render::TextureCreator, video::WindowContext};
+
+use super::{animation::AnimationId, GameInfo};
+
+/// Frames between checks for changed files
+const CHECK_INTERVAL: usize = 30;
+
+/// Reloads animations, stats and scripts into the game info when their files change. Only used
+/// in dev mode, since reloading while playing online would desync the players.
+pub struct HotReload {
+    texture_creator: TextureCreator<WindowContext>,
+    /// Modification time of every watched file as of the last check
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+    frames_until_check: usize,
+}
+
+impl HotReload {
+    pub fn new(info: &GameInfo, texture_creator: TextureCreator<WindowContext>) -> Self {
+        let mut hot_reload = Self {
+            texture_creator,
+            modified: HashMap::new(),
+            frames_until_check: CHECK_INTERVAL,
+        };
+        hot_reload.changed_files(info);
+        hot_reload
+    }
+
+    /// Reloads whatever changed since the last check. Returns whether anything was reloaded.
+    pub fn poll(&mut self, info: &mut GameInfo) -> bool {
+        if self.frames_until_check > 0 {
+            self.frames_until_check -= 1;
+            return false;
+        }
+        self.frames_until_check = CHECK_INTERVAL;
+
+        let changed = self.changed_files(info);
+        if changed.is_empty() {
+            return false;
+        }
+
+        let mut reloaded = false;
+        for proto in info.character_protos.values_mut() {
+            // The animations of a character share an atlas, so they are all reloaded together
+            let ids: Vec<AnimationId> = proto.animations.values().copied().collect();
+            if ids.iter().any(|&id| changed.contains(info.animations.path(id))) {
+                match info.animations.reload_group(
+                    &ids,
+                    &proto.palettes,
+                    Some(&self.texture_creator),
+                    |animation| proto.check_frame_events(animation),
+                ) {
+                    Ok(()) => {
+                        report(format_args!("reloaded animations of {}", proto.name));
+                        reloaded = true;
+                    }
+                    Err(e) => report(format_args!("couldn't reload animations: {e}")),
+                }
+            }
+
+            let data_changed = changed.contains(Path::new(proto.stats_path))
+                || proto
+                    .script_path
+                    .is_some_and(|path| changed.contains(Path::new(path)));
+            if data_changed {
+                // Keeps the old data if the new data is invalid
+                match proto.reload_data() {
+                    Ok(()) => {
+                        report(format_args!("reloaded data of {}", proto.name));
+                        reloaded = true;
+                    }
+                    Err(e) => report(format_args!("couldn't reload data: {e}")),
+                }
+            }
+        }
+        reloaded
+    }
+
+    /// Files that were modified since the last call
+    fn changed_files(&mut self, info: &GameInfo) -> HashSet<PathBuf> {
+        let animation_paths = info
+            .character_protos
+            .values()
+            .flat_map(|proto| proto.animations.values())
+            .map(|&id| info.animations.path(id).to_path_buf());
+        let data_paths = info.character_protos.values().flat_map(|proto| {
+            [Some(proto.stats_path), proto.script_path]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::from)
+        });
+
+        let mut changed = HashSet::new();
+        for path in animation_paths.chain(data_paths) {
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if let Some(old) = self.modified.insert(path.clone(), modified) {
+                if old != modified {
+                    changed.insert(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// Every hot reload message, successful or not, goes through here so they all show up in one
+/// place in the same form
+fn report(message: impl Display) {
+    eprintln!("hot reload: {message}");
+}
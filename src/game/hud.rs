@@ -0,0 +1,95 @@
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+/// Which of the seven segments (top, top right, bottom right, bottom, bottom left, top left,
+/// middle) are lit for each digit
+const SEGMENTS: [[bool; 7]; 10] = [
+    [true, true, true, true, true, true, false],
+    [false, true, true, false, false, false, false],
+    [true, true, false, true, true, false, true],
+    [true, true, true, true, false, false, true],
+    [false, true, true, false, false, true, true],
+    [true, false, true, true, false, true, true],
+    [true, false, true, true, true, true, true],
+    [true, true, true, false, false, false, false],
+    [true, true, true, true, true, true, true],
+    [true, true, true, true, false, true, true],
+];
+
+/// Draws a number as seven-segment digits with its top left corner at the given position.
+/// Returns the width of the drawn number.
+pub fn draw_number(
+    canvas: &mut Canvas<Window>,
+    x: i32,
+    y: i32,
+    height: u32,
+    number: usize,
+    color: Color,
+) -> u32 {
+    let (segments, width) = number_segments(x, y, height, number);
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&segments).unwrap();
+    width
+}
+
+/// The lit segments of a number drawn like `draw_number` does, and the width of the number
+pub fn number_segments(x: i32, y: i32, height: u32, number: usize) -> (Vec<Rect>, u32) {
+    let digits: Vec<usize> = number
+        .to_string()
+        .bytes()
+        .map(|b| (b - b'0') as usize)
+        .collect();
+
+    let width = height / 2;
+    let thickness = (height / 10).max(1);
+    let spacing = thickness * 2;
+    let half = (height - thickness) / 2;
+
+    let mut lit_segments = vec![];
+    let mut offset = x;
+    for digit in digits {
+        let segments = [
+            Rect::new(offset, y, width, thickness),
+            Rect::new(offset + (width - thickness) as i32, y, thickness, half + thickness),
+            Rect::new(
+                offset + (width - thickness) as i32,
+                y + half as i32,
+                thickness,
+                height - half,
+            ),
+            Rect::new(offset, y + (height - thickness) as i32, width, thickness),
+            Rect::new(offset, y + half as i32, thickness, height - half),
+            Rect::new(offset, y, thickness, half + thickness),
+            Rect::new(offset, y + half as i32, width, thickness),
+        ];
+
+        for (segment, lit) in segments.into_iter().zip(SEGMENTS[digit]) {
+            if lit {
+                lit_segments.push(segment);
+            }
+        }
+
+        offset += (width + spacing) as i32;
+    }
+
+    (lit_segments, (offset - x) as u32 - spacing)
+}
+
+/// Draws `area` filled to `fraction` of its width, starting from its right end if `from_right`
+pub fn draw_bar(
+    canvas: &mut Canvas<Window>,
+    area: Rect,
+    fraction: f64,
+    from_right: bool,
+    color: Color,
+) {
+    let width = (fraction.clamp(0.0, 1.0) * area.width() as f64) as u32;
+    let x = if from_right {
+        area.right() - width as i32
+    } else {
+        area.x()
+    };
+    canvas.set_draw_color(color);
+    canvas
+        .fill_rect(Rect::new(x, area.y(), width, area.height()))
+        .unwrap();
+}
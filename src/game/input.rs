@@ -195,6 +195,49 @@ impl InputHistory {
         self.events.push(event);
     }
 
+    /// Whether the given direction was just pressed for the second time, with only neutral in
+    /// between, all within `window` frames
+    pub fn double_tap(&self, dir: InputDirection, window: usize, current_frame: Frame) -> bool {
+        let mut presses = self.iter(window, current_frame).filter_map(|ie| match ie.kind {
+            InputKind::Direction(dir) if ie.pressed => Some((dir, ie.frame)),
+            _ => None,
+        });
+
+        match (presses.next(), presses.next(), presses.next()) {
+            (Some((second, frame)), Some((InputDirection::Neutral, _)), Some((first, _))) => {
+                second == dir && first == dir && current_frame.since_with_freeze(frame) == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// The direction pressed this frame, if the direction changed
+    pub fn direction_pressed_now(&self, current_frame: Frame) -> Option<InputDirection> {
+        self.iter(0, current_frame).find_map(|ie| match ie.kind {
+            InputKind::Direction(dir) if ie.pressed => Some(dir),
+            _ => None,
+        })
+    }
+
+    /// Whether the latest direction pressed is upwards, with a downwards direction pressed
+    /// before it within `window` frames
+    pub fn down_up(&self, window: usize, current_frame: Frame) -> bool {
+        let mut presses = self.iter(window, current_frame).filter_map(|ie| match ie.kind {
+            InputKind::Direction(dir) if ie.pressed => Some(dir),
+            _ => None,
+        });
+
+        matches!(
+            presses.next(),
+            Some(InputDirection::UpLeft | InputDirection::Up | InputDirection::UpRight)
+        ) && presses.any(|dir| {
+            matches!(
+                dir,
+                InputDirection::DownLeft | InputDirection::Down | InputDirection::DownRight
+            )
+        })
+    }
+
     pub fn iter(&self, allowed_diff: usize, current_frame: Frame) -> InputIter {
         InputIter {
             iter: self.events.iter().rev(),
@@ -265,3 +308,70 @@ pub enum Button {
     Punch,
     Kick,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{InputDirection::*, *};
+
+    fn frame(number: usize) -> Frame {
+        let mut frame = Frame::new();
+        for _ in 0..number {
+            frame.tick(false);
+        }
+        frame
+    }
+
+    /// History of the directions pressed, each on the given frame
+    fn history(presses: &[(usize, InputDirection)]) -> InputHistory {
+        let mut history = InputHistory::new();
+        for &(number, dir) in presses {
+            history.add(InputEvent {
+                frame: frame(number),
+                kind: InputKind::Direction(dir),
+                pressed: true,
+            });
+        }
+        history
+    }
+
+    #[test]
+    fn double_tap_needs_neutral_between_the_presses() {
+        let tapped = history(&[(0, Right), (2, Neutral), (4, Right)]);
+        assert!(tapped.double_tap(Right, 10, frame(4)));
+        assert!(!tapped.double_tap(Left, 10, frame(4)));
+
+        let held = history(&[(0, Right), (2, DownRight), (4, Right)]);
+        assert!(!held.double_tap(Right, 10, frame(4)));
+
+        let switched = history(&[(0, Left), (2, Neutral), (4, Right)]);
+        assert!(!switched.double_tap(Right, 10, frame(4)));
+    }
+
+    #[test]
+    fn double_tap_only_counts_on_the_frame_of_the_second_press() {
+        let tapped = history(&[(0, Right), (2, Neutral), (4, Right)]);
+        assert!(!tapped.double_tap(Right, 10, frame(5)));
+    }
+
+    #[test]
+    fn double_tap_must_fit_in_the_window() {
+        let tapped = history(&[(0, Right), (2, Neutral), (12, Right)]);
+        assert!(!tapped.double_tap(Right, 10, frame(12)));
+        assert!(tapped.double_tap(Right, 12, frame(12)));
+    }
+
+    #[test]
+    fn down_up_needs_up_after_down() {
+        assert!(history(&[(0, Down), (3, Up)]).down_up(10, frame(3)));
+        assert!(history(&[(0, DownLeft), (1, Neutral), (3, UpRight)]).down_up(10, frame(3)));
+        assert!(!history(&[(0, Neutral), (3, Up)]).down_up(10, frame(3)));
+        assert!(!history(&[(0, Up), (3, Down)]).down_up(10, frame(3)));
+    }
+
+    #[test]
+    fn down_up_must_fit_in_the_window() {
+        let presses = history(&[(0, Down), (12, Up)]);
+        assert!(!presses.down_up(10, frame(12)));
+        assert!(presses.down_up(12, frame(12)));
+    }
+}
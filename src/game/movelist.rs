@@ -3,7 +3,7 @@ use super::{
     time::Frame, fixed_point::FixedPoint,
 };
 
-use super::character::CharacterState;
+use super::character::{CharacterState, State};
 
 pub struct Movelist {
     moves: Vec<Move>,
@@ -16,16 +16,23 @@ impl Movelist {
         }
     }
 
+    /// Finds the move with the highest priority that can be performed. While a cancel window is
+    /// open, moves don't need to match the state, only to go to a state with a higher priority.
     pub fn perform(
         &self,
         frame: Frame,
         state: &CharacterState,
         input: &InputHistory,
+        meter: FixedPoint,
+        cancel_window: bool,
     ) -> Option<&Move> {
         self.moves
             .iter()
             .filter(|mov| {
-                mov.state_matcher.matches(state) && mov.input_matcher.matches(frame, input)
+                let cancels = cancel_window && mov.new_state.priority() > state.priority();
+                mov.meter_cost <= meter
+                    && (mov.state_matcher.matches(state) || cancels)
+                    && mov.input_matcher.matches(frame, input)
             })
             .max_by_key(|mov| mov.priority)
     }
@@ -38,6 +45,14 @@ pub struct Move {
     pub priority: usize,
     pub new_state: CharacterState,
     pub stops_momentum: bool,
+    /// Meter required to perform the move, which is spent when it is performed
+    pub meter_cost: FixedPoint,
+    /// Meter gained when the move is performed, whether it hits or not
+    pub meter_gain: FixedPoint,
+    /// Amount of frames both players are frozen for when the move is performed
+    pub super_freeze: usize,
+    /// Effects applied to the player when the move is performed
+    pub self_effects: Vec<Effect>,
 }
 
 pub struct InputMatcher {
@@ -66,6 +81,21 @@ impl InputMatcher {
                 .is_some()
         })
     }
+
+    /// Matches when all of the given buttons have been pressed within the input buffer
+    pub fn button_combo(buttons: Vec<Button>) -> Self {
+        Self::new(move |frame, history| {
+            buttons.iter().all(|&button| {
+                history
+                    .iter(INPUT_BUFFER, frame)
+                    .find(|ie| ie.pressed && match ie.kind {
+                        super::input::InputKind::Button(btn) => btn == button,
+                        _ => false,
+                    })
+                    .is_some()
+            })
+        })
+    }
 }
 
 pub struct StateMatcher {
@@ -108,18 +138,72 @@ impl StateMatcher {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct HitEffect {
     pub unblockable: bool,
     pub effects_on_hit: Vec<Effect>,
     pub effects_on_block: Vec<Effect>,
+    /// Effects applied to the attacker instead of the target
+    pub self_effects_on_hit: Vec<Effect>,
+    pub self_effects_on_block: Vec<Effect>,
+    /// Meter gained by the attacker when the attack hits
+    pub meter_gain_on_hit: FixedPoint,
+    /// Meter gained by the attacker when the attack is blocked
+    pub meter_gain_on_block: FixedPoint,
+    /// Applied when the target is hit during the startup or active frames of their own attack
+    pub counter_hit: CounterModifiers,
+    /// Applied when the target is hit during the recovery of their own attack
+    pub punish_counter: CounterModifiers,
+    /// Damage scaling applied to the rest of the combo when this hit starts it
+    pub starter_scaling: FixedPoint,
+}
+
+/// Multipliers applied to the damage and hitstun effects of a counter hit
+#[derive(Debug, Clone, Copy)]
+pub struct CounterModifiers {
+    pub damage: FixedPoint,
+    pub hitstun: FixedPoint,
+}
+
+impl CounterModifiers {
+    pub const NONE: Self = Self {
+        damage: FixedPoint::ONE,
+        hitstun: FixedPoint::ONE,
+    };
+
+    pub fn apply(self, effect: &Effect) -> Effect {
+        match effect {
+            Effect::Damage(amt) => Effect::Damage(*amt * self.damage),
+            Effect::Hitstun(amt) => {
+                Effect::Hitstun(i32::from(FixedPoint::from(*amt) * self.hitstun) as usize)
+            }
+            effect => effect.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterKind {
+    CounterHit,
+    PunishCounter,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum Effect {
     Damage(FixedPoint),
     Hitstun(usize),
     Blockstun(usize),
     Knockback(FixedPoint),
+    /// Pulls the player towards the direction they are facing
+    PullIn(FixedPoint),
+    /// Bounces the player off the next wall they touch, with the given upwards velocity
+    WallBounce(FixedPoint),
+    /// Bounces the player off the ground the next time they land, with the given upwards velocity
+    GroundBounce(FixedPoint),
     StateChange(CharacterState),
+    /// Damage dealt on block, which can't KO unless `GameInfo::chip_can_kill` is set
+    ChipDamage(FixedPoint),
+    /// Drains the guard gauge, guard crushing the player when it runs out. Should come after
+    /// any `Blockstun` effect, so the guard crush takes precedence.
+    GuardDamage(FixedPoint),
 }
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, path::Path};
+
+use image::RgbaImage;
+
+use super::data_error::{DataError, DataErrorKind};
+
+/// A recoloring of a character's sprites
+pub struct Palette {
+    /// Maps colors in the original sprites to the colors to draw instead
+    pub remap: HashMap<[u8; 3], [u8; 3]>,
+}
+
+impl Palette {
+    /// Loads every palette of a character. A palette file lists the remappable colors once and
+    /// then, for each palette, the replacement for each of those colors in the same order.
+    pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>, DataError> {
+        let path = path.as_ref();
+        let file: interface::PaletteFile = DataError::read_json(path)?;
+
+        if file.palettes.len() < 2 {
+            return Err(DataError::new(
+                path,
+                DataErrorKind::TooFewPalettes {
+                    count: file.palettes.len(),
+                },
+            ));
+        }
+
+        file.palettes
+            .into_iter()
+            .map(|palette| {
+                if palette.colors.len() != file.base.len() {
+                    return Err(DataError::new(
+                        path,
+                        DataErrorKind::PaletteColorCount {
+                            palette: palette.name,
+                            expected: file.base.len(),
+                            found: palette.colors.len(),
+                        },
+                    ));
+                }
+                Ok(Self {
+                    remap: file
+                        .base
+                        .iter()
+                        .copied()
+                        .zip(palette.colors)
+                        .filter(|(from, to)| from != to)
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn apply(&self, img: &mut RgbaImage) {
+        if self.remap.is_empty() {
+            return;
+        }
+
+        for pixel in img.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            if let Some(&[r, g, b]) = self.remap.get(&[r, g, b]) {
+                pixel.0 = [r, g, b, a];
+            }
+        }
+    }
+}
+
+mod interface {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PaletteFile {
+        pub base: Vec<[u8; 3]>,
+        pub palettes: Vec<Palette>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Palette {
+        pub name: String,
+        pub colors: Vec<[u8; 3]>,
+    }
+}
@@ -0,0 +1,109 @@
+use std::hash::{Hash, Hasher};
+
+use sdl2::{render::Canvas, video::Window};
+
+use super::{
+    animation::AnimationStore,
+    camera::Camera,
+    character::Facing,
+    fixed_point::{FixedPoint, Vec2},
+    movelist::HitEffect,
+    time::Frame,
+    Animator, Hitbox, Hurtbox, PlayerSide, STAGE_HALF_WIDTH,
+};
+
+/// Describes a projectile to spawn, relative to the player spawning it
+#[derive(Debug, Clone)]
+pub struct ProjectileSpawn {
+    /// Name of the animation in the spawning character's animation list
+    pub animation: &'static str,
+    /// Spawn position relative to the player, as if facing right
+    pub offset: Vec2,
+    /// Velocity of the projectile, as if facing right
+    pub velocity: Vec2,
+    pub hit_effect: HitEffect,
+    /// How many times the projectile can hit before disappearing
+    pub hits: usize,
+    /// Frames between each hit of a multi-hit projectile
+    pub hit_interval: usize,
+    /// Frames until the projectile disappears on its own
+    pub lifetime: usize,
+}
+
+#[derive(Clone)]
+pub struct Projectile {
+    pub owner: PlayerSide,
+    pub animator: Animator,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub facing: Facing,
+    pub palette: usize,
+    pub hitboxes: Vec<Hitbox>,
+    pub hurtboxes: Vec<Hurtbox>,
+    pub hit_effect: HitEffect,
+    pub hits_left: usize,
+    pub hit_interval: usize,
+    pub last_hit: Option<Frame>,
+    pub lifetime: usize,
+}
+
+impl Hash for Projectile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.owner.hash(state);
+        self.animator.hash(state);
+        self.position.hash(state);
+        self.velocity.hash(state);
+        self.hits_left.hash(state);
+        self.last_hit.hash(state);
+        self.lifetime.hash(state);
+    }
+}
+
+impl Projectile {
+    pub fn can_hit(&self, frame: Frame) -> bool {
+        self.hits_left > 0
+            && self
+                .last_hit
+                .is_none_or(|last| frame.since_without_freeze(last) >= self.hit_interval)
+    }
+
+    pub fn register_hit(&mut self, frame: Frame) {
+        self.hits_left = self.hits_left.saturating_sub(1);
+        self.last_hit = Some(frame);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.hits_left > 0
+            && self.lifetime > 0
+            && self.position.x.abs() <= FixedPoint::from(STAGE_HALF_WIDTH * 2)
+    }
+
+    pub fn tick(&mut self, animations: &AnimationStore, frame: Frame) {
+        let delta = FixedPoint::from(1.0 / 60.0);
+
+        // Frame events only do anything for players
+        self.position += self
+            .animator
+            .tick(
+                animations,
+                self.facing,
+                &mut self.hitboxes,
+                &mut self.hurtboxes,
+                frame,
+            )
+            .root_motion;
+        self.position += self.velocity * delta;
+        self.lifetime = self.lifetime.saturating_sub(1);
+    }
+
+    pub fn render(&self, camera: &Camera, animations: &AnimationStore, canvas: &mut Canvas<Window>) {
+        self.animator.render(
+            animations,
+            self.position,
+            self.facing,
+            self.palette,
+            camera,
+            canvas,
+        );
+    }
+}
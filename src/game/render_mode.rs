@@ -0,0 +1,136 @@
+use clap::ValueEnum;
+use sdl2::{
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::Window,
+};
+
+/// Size of the visible part of the world, in world units
+pub const SCENE_WIDTH: u32 = 256;
+pub const SCENE_HEIGHT: u32 = 144;
+
+/// How the scene is scaled to fit the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// Drawn straight to the window at whatever scale fits it
+    Direct,
+    /// Drawn at one pixel per world unit, then scaled up by the largest whole number that fits
+    Integer,
+    /// Drawn at one pixel per world unit, then stretched to fit without smoothing
+    Nearest,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Direct => RenderMode::Integer,
+            RenderMode::Integer => RenderMode::Nearest,
+            RenderMode::Nearest => RenderMode::Direct,
+        }
+    }
+
+    /// Whether the scene is drawn to a `SceneTarget` first
+    pub fn uses_target(self) -> bool {
+        self != RenderMode::Direct
+    }
+
+    /// The part of a window of the given size the scene is shown in, with black bars around it
+    pub fn viewport(self, width: u32, height: u32) -> Rect {
+        let (scene_width, scene_height) = match self {
+            RenderMode::Direct | RenderMode::Nearest => {
+                if width * SCENE_HEIGHT > height * SCENE_WIDTH {
+                    (height * SCENE_WIDTH / SCENE_HEIGHT, height)
+                } else {
+                    (width, width * SCENE_HEIGHT / SCENE_WIDTH)
+                }
+            }
+            RenderMode::Integer => {
+                let scale = (width / SCENE_WIDTH).min(height / SCENE_HEIGHT);
+                if scale == 0 {
+                    // Too small for whole pixels, so the scene is shrunk instead
+                    return RenderMode::Nearest.viewport(width, height);
+                }
+                (SCENE_WIDTH * scale, SCENE_HEIGHT * scale)
+            }
+        };
+
+        Rect::new(
+            (width as i32 - scene_width as i32) / 2,
+            (height as i32 - scene_height as i32) / 2,
+            scene_width,
+            scene_height,
+        )
+    }
+}
+
+/// Low resolution texture the scene is drawn to before it is scaled to the window, created when
+/// it is first needed
+pub struct SceneTarget {
+    texture: Option<Texture>,
+}
+
+impl SceneTarget {
+    pub fn new() -> Self {
+        Self { texture: None }
+    }
+
+    /// Draws the scene to the texture with `draw`, then copies the texture to `viewport`
+    pub fn render(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        viewport: Rect,
+        draw: impl FnOnce(&mut Canvas<Window>),
+    ) {
+        let texture = self.texture.get_or_insert_with(|| {
+            // Only applies to textures created after it is set
+            sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+            canvas
+                .texture_creator()
+                .create_texture_target(PixelFormatEnum::RGBA8888, SCENE_WIDTH, SCENE_HEIGHT)
+                .unwrap()
+        });
+
+        canvas.with_texture_canvas(texture, draw).unwrap();
+        canvas.copy(texture, None, viewport).unwrap();
+    }
+}
+
+impl Drop for SceneTarget {
+    fn drop(&mut self) {
+        if let Some(texture) = self.texture.take() {
+            // SAFETY: the target is owned by the `GameRunner`, which is dropped before the canvas
+            // whose renderer created the texture
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_viewport_uses_largest_whole_scale() {
+        assert_eq!(
+            RenderMode::Integer.viewport(1280, 720),
+            Rect::new(0, 0, 1280, 720)
+        );
+        assert_eq!(
+            RenderMode::Integer.viewport(1000, 600),
+            Rect::new(116, 84, 768, 432)
+        );
+    }
+
+    #[test]
+    fn integer_viewport_shrinks_in_small_windows() {
+        assert_eq!(
+            RenderMode::Integer.viewport(200, 144),
+            RenderMode::Nearest.viewport(200, 144)
+        );
+        assert_eq!(
+            RenderMode::Integer.viewport(200, 144),
+            Rect::new(0, 16, 200, 112)
+        );
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Copy, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Hash, Serialize, Deserialize)]
 pub struct Frame {
     frame_with_freeze: usize,
     frame_without_freeze: usize,
@@ -19,6 +21,11 @@ impl Frame {
         }
     }
 
+    /// Frames since the start of the match, including frozen ones
+    pub fn number(self) -> usize {
+        self.frame_with_freeze
+    }
+
     pub fn since_without_freeze(self, frame: Frame) -> usize {
         self.frame_without_freeze - frame.frame_without_freeze
     }
@@ -0,0 +1,67 @@
+use sdl2::{render::Canvas, video::Window};
+
+use super::{
+    animation::{AnimationId, AnimationStore},
+    camera::Camera,
+    character::Facing,
+    fixed_point::Vec2,
+    time::Frame,
+    Animator,
+};
+
+/// An animation played once at a position, without any gameplay effect
+#[derive(Clone)]
+pub struct VisualEffect {
+    animator: Animator,
+    position: Vec2,
+    facing: Facing,
+    palette: usize,
+    /// Frames until the animation has played through
+    frames_left: usize,
+}
+
+impl VisualEffect {
+    pub fn new(
+        animations: &AnimationStore,
+        animation: AnimationId,
+        frame: Frame,
+        position: Vec2,
+        facing: Facing,
+        palette: usize,
+    ) -> Self {
+        Self {
+            animator: Animator::new(frame, animation),
+            position,
+            facing,
+            palette,
+            frames_left: animations[animation].duration(),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.frames_left > 0
+    }
+
+    pub fn tick(&mut self, animations: &AnimationStore, frame: Frame) {
+        // Effects have no boxes and don't move on their own, so only the frame matters
+        self.animator
+            .tick(animations, self.facing, &mut vec![], &mut vec![], frame);
+        self.frames_left = self.frames_left.saturating_sub(1);
+    }
+
+    /// Moves back to the last frame if the animation got shorter when it was reloaded
+    pub fn clamp_frame(&mut self, animations: &AnimationStore) {
+        self.animator.clamp_frame(animations);
+    }
+
+    pub fn render(&self, camera: &Camera, animations: &AnimationStore, canvas: &mut Canvas<Window>) {
+        self.animator.render(
+            animations,
+            self.position,
+            self.facing,
+            self.palette,
+            camera,
+            canvas,
+        );
+    }
+}
@@ -13,26 +13,110 @@ mod updater;
 use std::{
     collections::HashMap,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
 use app_context::AppContext;
 use asset_manager::AssetManager;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use game::{PlayerSide, FPS};
 use ggrs::{SessionBuilder, SessionState, UdpNonBlockingSocket};
 use sdl2::{event::Event, keyboard::Keycode, pixels::Color};
 
-use crate::game::{GameInfo, GameRunner, GameState, PlayerType};
+use crate::{
+    game::{
+        Character, CharacterChoice, CharacterSelect, GameInfo, GameRunner, GameState, HotReload,
+        PlayerType, RenderMode,
+    },
+    state::{State, StateStack},
+};
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Opts {
     player_side: Option<PlayerSide>,
+    /// Reload animations and character data when their files change
+    #[arg(long)]
+    dev: bool,
+    /// How the game is scaled to the window. Can be changed with F4 during a match.
+    #[arg(long, value_enum, default_value_t = RenderMode::Direct)]
+    render_mode: RenderMode,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Converts an animation between the JSON and binary forms
+    ConvertAnim {
+        input: PathBuf,
+        output: PathBuf,
+        /// Store the spritesheet uncompressed when converting to binary
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Renders an animation to a PNG for every frame, or to an animated GIF if the output ends
+    /// in .gif
+    RenderAnim {
+        input: PathBuf,
+        /// Directory for the PNGs, or the GIF file
+        output: PathBuf,
+        /// Draw the hurtboxes in green and the hitboxes in red
+        #[arg(long)]
+        hitboxes: bool,
+        /// Draw the index of each frame in its top left corner
+        #[arg(long)]
+        frame_numbers: bool,
+        /// Size of a spritesheet pixel in the output
+        #[arg(long, default_value_t = 1)]
+        scale: u32,
+    },
+    /// Checks animations for broken data and prints their frame data
+    LintAnims {
+        #[arg(default_value = "assets/animations")]
+        dir: PathBuf,
+    },
 }
 
 fn main() {
     let opts = Opts::parse();
 
+    match opts.command {
+        Some(Command::ConvertAnim { input, output, raw }) => {
+            if let Err(e) = game::animation::convert(&input, &output, raw) {
+                eprintln!("Couldn't convert {}: {e}", input.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::RenderAnim {
+            input,
+            output,
+            hitboxes,
+            frame_numbers,
+            scale,
+        }) => {
+            let options = game::animation::ExportOptions {
+                hitboxes,
+                frame_numbers,
+                scale,
+            };
+            if let Err(e) = game::animation::export(&input, &output, &options) {
+                eprintln!("Couldn't render {}: {e}", input.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::LintAnims { dir }) => {
+            if !lint_anims(&dir) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let game_controller_subsystem = sdl_context.game_controller().unwrap();
@@ -53,22 +137,47 @@ fn main() {
     let mut canvas = window.into_canvas().build().unwrap();
     canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
 
-    let texture_creator = Box::leak(Box::new(canvas.texture_creator()));
+    let texture_creator = canvas.texture_creator();
 
     let mut app_context = AppContext {
         sdl_context: &sdl_context,
         video_subsystem: &video_subsystem,
         game_controller_subsystem: &game_controller_subsystem,
     };
-    let mut game_info = GameInfo::create(texture_creator);
+    let mut game_info = match GameInfo::create(Some(&texture_creator)) {
+        Ok(game_info) => game_info,
+        Err(e) => {
+            eprintln!("Couldn't load game data: {e}");
+            std::process::exit(1);
+        }
+    };
     let PlayerType::Local { mapping, input } = &mut game_info.player_2 else {
         unreachable!()
     };
     mapping.grab_controller(0);
+    game_info.render_mode = opts.render_mode;
 
-    let game = GameState::new(&game_info);
+    if opts.dev {
+        if opts.player_side.is_some() {
+            eprintln!("Hot reloading is disabled in netplay, since it would desync the players");
+        } else {
+            game_info.hot_reload = Some(HotReload::new(&game_info, canvas.texture_creator()));
+        }
+    }
 
-    let mut runner = GameRunner::new(opts.player_side, game_info, game);
+    // Netplay has no way to agree on characters yet, so it skips character select
+    let initial_state: Box<dyn State> = match opts.player_side {
+        Some(_) => {
+            let choice = CharacterChoice {
+                character: Character::Guy,
+                palette: 0,
+            };
+            let game = GameState::new(&game_info, choice, choice);
+            Box::new(GameRunner::new(opts.player_side, game_info, game))
+        }
+        None => Box::new(CharacterSelect::new(opts.player_side, game_info)),
+    };
+    let mut state_stack = StateStack::new(initial_state);
 
     canvas.set_draw_color(Color::RGB(0, 255, 255));
     canvas.clear();
@@ -88,40 +197,6 @@ fn main() {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => break 'running,
-                Event::KeyDown {
-                    timestamp,
-                    window_id,
-                    keycode,
-                    scancode,
-                    keymod,
-                    repeat,
-                } => {
-                    runner.input(scancode.unwrap(), true);
-                }
-                Event::KeyUp {
-                    timestamp,
-                    window_id,
-                    keycode,
-                    scancode,
-                    keymod,
-                    repeat,
-                } => {
-                    runner.input(scancode.unwrap(), false);
-                }
-                Event::ControllerButtonDown {
-                    timestamp,
-                    which,
-                    button,
-                } => {
-                    runner.input(button, true);
-                }
-                Event::ControllerButtonUp {
-                    timestamp,
-                    which,
-                    button,
-                } => {
-                    runner.input(button, false);
-                }
                 Event::ControllerDeviceAdded { timestamp, which } => {
                     println!("Controller added: {which}");
                     controllers.insert(which, game_controller_subsystem.open(which).unwrap());
@@ -130,7 +205,7 @@ fn main() {
                     println!("Controller removed: {which}");
                     controllers.remove(&which);
                 }
-                _ => {}
+                event => state_stack.event(event, &mut app_context),
             }
         }
 
@@ -150,9 +225,43 @@ fn main() {
             // println!("{fps:.1}");
 
             let now = Instant::now();
-            runner.tick(&mut canvas);
+            state_stack.tick(&mut app_context);
+            state_stack.render(&mut canvas, &mut app_context);
+            canvas.present();
             let time = Instant::now().duration_since(now);
             // println!("{:?}", time)
         }
     }
 }
+
+/// Lints every animation in the directory, returning whether they were all fine
+fn lint_anims(dir: &std::path::Path) -> bool {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "anim"))
+            .collect(),
+        Err(e) => {
+            eprintln!("Couldn't read {}: {e}", dir.display());
+            return false;
+        }
+    };
+    paths.sort();
+
+    let mut ok = true;
+    for path in paths {
+        let report = game::animation::lint(&path);
+        match report.phases {
+            Some((startup, active, recovery)) => println!(
+                "{}: startup {startup}, active {active}, recovery {recovery}",
+                path.display()
+            ),
+            None => println!("{}", path.display()),
+        }
+        for problem in &report.problems {
+            println!("  error: {problem}");
+        }
+        ok &= report.problems.is_empty();
+    }
+    ok
+}